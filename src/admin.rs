@@ -0,0 +1,522 @@
+// src/admin.rs
+//! Minimal client for Caddy's admin API, over TCP or a unix socket.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Time allowed to connect to the admin endpoint
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time allowed for Caddy to answer (loading a config can provision certificates)
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Error returned by the admin API client
+#[derive(Debug)]
+pub enum AdminError {
+    /// The admin endpoint could not be reached (Caddy is probably not running)
+    Connect { address: String, message: String },
+    /// Caddy answered with an error status; `body` is Caddy's response, verbatim
+    Api { status: u16, body: String },
+    /// The response could not be understood
+    Protocol(String),
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::Connect { address, message } => {
+                write!(
+                    f,
+                    "Cannot reach Caddy admin API at '{}': {}",
+                    address, message
+                )
+            }
+            AdminError::Api { status, body } => {
+                write!(
+                    f,
+                    "Caddy admin API returned HTTP {}:\n{}",
+                    status,
+                    body.trim_end()
+                )
+            }
+            AdminError::Protocol(message) => write!(f, "Invalid admin API response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+/// Where the admin API listens
+#[derive(Debug, Clone)]
+enum Endpoint {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+/// A response from the admin API
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+/// A Caddyfile adapted to JSON by Caddy
+#[derive(Debug)]
+pub struct Adapted {
+    /// Caddy JSON configuration
+    pub config: String,
+    /// Adapter warnings, formatted as `file:line: message`
+    pub warnings: Vec<String>,
+}
+
+/// Client for Caddy's admin API
+#[derive(Debug, Clone)]
+pub struct AdminClient {
+    address: String,
+    endpoint: Endpoint,
+}
+
+impl AdminClient {
+    /// Create a client for an admin address in Caddy's format:
+    /// `localhost:2019`, `:2019`, `tcp/localhost:2019` or `unix//run/caddy.sock`
+    pub fn new(address: &str) -> Self {
+        let trimmed = address
+            .trim()
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+        let endpoint = match trimmed.strip_prefix("unix/") {
+            // Drop optional socket permission bits (`unix//run/caddy.sock|0220`)
+            Some(path) => Endpoint::Unix(PathBuf::from(path.split('|').next().unwrap_or(path))),
+            None => {
+                let host = trimmed.strip_prefix("tcp/").unwrap_or(trimmed);
+                if host.starts_with(':') {
+                    Endpoint::Tcp(format!("localhost{}", host))
+                } else {
+                    Endpoint::Tcp(host.to_string())
+                }
+            }
+        };
+        Self {
+            address: address.to_string(),
+            endpoint,
+        }
+    }
+
+    /// Send a request and return the response, whatever its status
+    pub fn request(
+        &self,
+        method: &str,
+        path: &str,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<Response, AdminError> {
+        // Caddy checks the Host header; requests over unix sockets use an empty one
+        let host = match &self.endpoint {
+            Endpoint::Tcp(host) => host.as_str(),
+            Endpoint::Unix(_) => "",
+        };
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            path,
+            host,
+            body.len()
+        );
+        if let Some(content_type) = content_type {
+            request.push_str(&format!("Content-Type: {}\r\n", content_type));
+        }
+        request.push_str("\r\n");
+
+        let mut raw = Vec::new();
+        self.exchange(request.as_bytes(), body, &mut raw)
+            .map_err(|e| AdminError::Connect {
+                address: self.address.clone(),
+                message: e.to_string(),
+            })?;
+        parse_response(&raw)
+    }
+
+    fn exchange(&self, head: &[u8], body: &[u8], raw: &mut Vec<u8>) -> std::io::Result<()> {
+        match &self.endpoint {
+            Endpoint::Tcp(host) => {
+                let addrs = std::net::ToSocketAddrs::to_socket_addrs(host.as_str())?;
+                let mut last_error = None;
+                for addr in addrs {
+                    match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                        Ok(mut stream) => {
+                            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                            stream.write_all(head)?;
+                            stream.write_all(body)?;
+                            stream.read_to_end(raw)?;
+                            return Ok(());
+                        }
+                        Err(e) => last_error = Some(e),
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "address did not resolve")
+                }))
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                stream.write_all(head)?;
+                stream.write_all(body)?;
+                stream.read_to_end(raw)?;
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            Endpoint::Unix(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "unix sockets are not supported on this platform",
+            )),
+        }
+    }
+
+    /// Send a request and fail on non-2xx statuses
+    fn request_ok(
+        &self,
+        method: &str,
+        path: &str,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<Response, AdminError> {
+        let response = self.request(method, path, content_type, body)?;
+        if (200..300).contains(&response.status) {
+            Ok(response)
+        } else {
+            Err(AdminError::Api {
+                status: response.status,
+                body: response.body,
+            })
+        }
+    }
+
+    /// Adapt a Caddyfile to Caddy JSON (`POST /adapt`)
+    pub fn adapt_caddyfile(&self, caddyfile: &str) -> Result<Adapted, AdminError> {
+        let response = self.request_ok(
+            "POST",
+            "/adapt",
+            Some("text/caddyfile"),
+            caddyfile.as_bytes(),
+        )?;
+        let value: serde_json::Value = serde_json::from_str(&response.body)
+            .map_err(|e| AdminError::Protocol(format!("adapt response is not JSON: {}", e)))?;
+        let config = value
+            .get("result")
+            .ok_or_else(|| AdminError::Protocol("adapt response has no 'result'".to_string()))?
+            .to_string();
+        let warnings = value
+            .get("warnings")
+            .and_then(|w| w.as_array())
+            .map(|warnings| warnings.iter().map(format_warning).collect())
+            .unwrap_or_default();
+        Ok(Adapted { config, warnings })
+    }
+
+    /// Replace Caddy's active configuration with a JSON config (`POST /load`)
+    pub fn load(&self, config_json: &str) -> Result<(), AdminError> {
+        self.request_ok(
+            "POST",
+            "/load",
+            Some("application/json"),
+            config_json.as_bytes(),
+        )
+        .map(|_| ())
+    }
+
+    /// Caddy's active JSON configuration (`GET /config/`)
+    pub fn config(&self) -> Result<serde_json::Value, AdminError> {
+        let response = self.request_ok("GET", "/config/", None, b"")?;
+        serde_json::from_str(&response.body)
+            .map_err(|e| AdminError::Protocol(format!("config is not JSON: {}", e)))
+    }
+
+    /// Gracefully stop Caddy (`POST /stop`)
+    pub fn stop(&self) -> Result<(), AdminError> {
+        self.request_ok("POST", "/stop", None, b"").map(|_| ())
+    }
+}
+
+/// Host names matched by the HTTP routes of a Caddy JSON configuration
+pub fn config_hosts(config: &serde_json::Value) -> Vec<String> {
+    let mut hosts: Vec<String> = Vec::new();
+    let servers = config
+        .pointer("/apps/http/servers")
+        .and_then(|servers| servers.as_object());
+    for server in servers.into_iter().flat_map(|servers| servers.values()) {
+        let routes = server.get("routes").and_then(|r| r.as_array());
+        for route in routes.into_iter().flatten() {
+            let matchers = route.get("match").and_then(|m| m.as_array());
+            for matcher in matchers.into_iter().flatten() {
+                let names = matcher.get("host").and_then(|h| h.as_array());
+                for name in names.into_iter().flatten().filter_map(|h| h.as_str()) {
+                    if !hosts.iter().any(|host| host == name) {
+                        hosts.push(name.to_string());
+                    }
+                }
+            }
+        }
+    }
+    hosts
+}
+
+/// Format an adapter warning (`{"file": ..., "line": ..., "message": ...}`)
+fn format_warning(warning: &serde_json::Value) -> String {
+    let file = warning.get("file").and_then(|v| v.as_str()).unwrap_or("");
+    let line = warning.get("line").and_then(|v| v.as_u64()).unwrap_or(0);
+    let message = warning
+        .get("message")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    match (file.is_empty(), line) {
+        (true, _) => message.to_string(),
+        (false, 0) => format!("{}: {}", file, message),
+        (false, line) => format!("{}:{}: {}", file, line, message),
+    }
+}
+
+/// Parse a raw HTTP/1.1 response, handling chunked transfer encoding
+fn parse_response(raw: &[u8]) -> Result<Response, AdminError> {
+    let head_end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| AdminError::Protocol("incomplete response headers".to_string()))?;
+    let head = String::from_utf8_lossy(&raw[..head_end]);
+    let mut lines = head.lines();
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| AdminError::Protocol("invalid status line".to_string()))?;
+
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+
+    let body = &raw[head_end + 4..];
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        body.to_vec()
+    };
+
+    Ok(Response {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>, AdminError> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| AdminError::Protocol("truncated chunk size".to_string()))?;
+        let size_line = String::from_utf8_lossy(&data[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| AdminError::Protocol(format!("invalid chunk size '{}'", size_hex)))?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if data.len() < size {
+            return Err(AdminError::Protocol("truncated chunk".to_string()));
+        }
+        body.extend_from_slice(&data[..size]);
+        data = data.get(size + 2..).unwrap_or(&[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::thread;
+
+    /// Serve a single request with a canned response, returning the listener's address
+    /// and the request line, headers and body it received
+    fn stub_server(response: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8(body).unwrap());
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn adapt_caddyfile_reads_result_and_warnings() {
+        let (address, server) = stub_server(concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"{"result":{"apps":{}},"warnings":["#,
+            r#"{"file":"Caddyfile","line":3,"message":"unnecessary header_up"},"#,
+            r#"{"file":"Caddyfile","message":"no line"},{"message":"bare"}]}"#,
+        ));
+
+        let adapted = AdminClient::new(&address)
+            .adapt_caddyfile("a.localhost {\n}\n")
+            .unwrap();
+
+        assert_eq!(adapted.config, r#"{"apps":{}}"#);
+        assert_eq!(
+            adapted.warnings,
+            [
+                "Caddyfile:3: unnecessary header_up",
+                "Caddyfile: no line",
+                "bare"
+            ]
+        );
+        let request = server.join().unwrap();
+        assert!(
+            request.starts_with("POST /adapt HTTP/1.1\r\n"),
+            "{}",
+            request
+        );
+        assert!(request.contains("Content-Type: text/caddyfile\r\n"));
+        assert!(request.ends_with("\r\n\r\na.localhost {\n}\n"));
+    }
+
+    #[test]
+    fn load_error_keeps_the_body_verbatim() {
+        let body = "{\"error\":\"loading config: ambiguous site definition: a.localhost\"}\n";
+        let (address, server) = stub_server(concat!(
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: 67\r\n\r\n",
+            "{\"error\":\"loading config: ambiguous site definition: a.localhost\"}\n",
+        ));
+
+        let error = AdminClient::new(&address).load("{}").unwrap_err();
+
+        match error {
+            AdminError::Api { status, body: got } => {
+                assert_eq!(status, 400);
+                assert_eq!(got, body);
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+        assert!(
+            server
+                .join()
+                .unwrap()
+                .starts_with("POST /load HTTP/1.1\r\n")
+        );
+    }
+
+    #[test]
+    fn config_reads_chunked_responses() {
+        let (address, server) = stub_server(concat!(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "5\r\n{\"a\":\r\n",
+            "3;ext=1\r\n[1]\r\n",
+            "1\r\n}\r\n",
+            "0\r\n\r\n",
+        ));
+
+        let config = AdminClient::new(&address).config().unwrap();
+
+        assert_eq!(config, serde_json::json!({"a": [1]}));
+        assert!(
+            server
+                .join()
+                .unwrap()
+                .starts_with("GET /config/ HTTP/1.1\r\n")
+        );
+    }
+
+    #[test]
+    fn parse_response_decodes_chunked_bodies() {
+        let raw = b"HTTP/1.1 404 Not Found\r\ntransfer-encoding: Chunked\r\n\r\n4\r\nWiki\r\nA\r\npedia in\r\n\r\n0\r\n\r\n";
+        let response = parse_response(raw).unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, "Wikipedia in\r\n");
+    }
+
+    #[test]
+    fn parse_response_keeps_plain_bodies() {
+        let response = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "ok");
+        assert!(matches!(
+            parse_response(b"HTTP/1.1 200 OK\r\n"),
+            Err(AdminError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn decode_chunked_rejects_truncated_data() {
+        assert_eq!(decode_chunked(b"3\r\nabc\r\n0\r\n\r\n").unwrap(), b"abc");
+        assert!(matches!(
+            decode_chunked(b"a\r\nabc"),
+            Err(AdminError::Protocol(_))
+        ));
+        assert!(matches!(
+            decode_chunked(b"zz\r\n"),
+            Err(AdminError::Protocol(_))
+        ));
+        assert!(matches!(decode_chunked(b"3"), Err(AdminError::Protocol(_))));
+    }
+
+    #[test]
+    fn refused_connection_is_a_connect_error() {
+        // Bind then drop a listener to get a port nothing listens on
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        match AdminClient::new(&address).config() {
+            Err(AdminError::Connect { address: got, .. }) => assert_eq!(got, address),
+            other => panic!("expected a connect error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn new_accepts_caddy_address_formats() {
+        assert!(matches!(
+            AdminClient::new(":2019").endpoint,
+            Endpoint::Tcp(ref host) if host == "localhost:2019"
+        ));
+        assert!(matches!(
+            AdminClient::new("tcp/127.0.0.1:2019").endpoint,
+            Endpoint::Tcp(ref host) if host == "127.0.0.1:2019"
+        ));
+        assert!(matches!(
+            AdminClient::new("unix//run/caddy.sock|0220").endpoint,
+            Endpoint::Unix(ref path) if path == Path::new("/run/caddy.sock")
+        ));
+    }
+}
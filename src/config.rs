@@ -0,0 +1,871 @@
+// src/config.rs
+//! caddy-dev configuration (`config.toml`) and the main Caddyfile derived from it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::CaddyDevError;
+use crate::generate::OUTPUT_FILE_NAME;
+use crate::vars::VARS_FILE_NAME;
+
+/// Configuration file name inside the config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Caddy's default admin endpoint
+pub const DEFAULT_ADMIN: &str = "localhost:2019";
+
+/// A Caddyfile.dev file matched by a configured folder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedFile {
+    /// Configured folder or glob pattern that matched the file
+    pub folder: String,
+    pub path: PathBuf,
+}
+
+/// A Caddyfile.dev matched by more than one configured folder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    pub path: PathBuf,
+    /// Configured folders or glob patterns matching the file, in import order
+    pub folders: Vec<String>,
+}
+
+/// caddy-dev configuration stored in `config.toml`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Caddy binary, either a name looked up in PATH or a full path
+    pub caddy_bin: String,
+
+    /// Address of Caddy's admin endpoint
+    pub admin: String,
+
+    /// How `caddy-dev reload` applies the configuration
+    pub reload: ReloadMethod,
+
+    /// Folders or glob patterns to import Caddyfile.dev files from
+    pub folders: Vec<String>,
+
+    /// Global options block written at the top of the main Caddyfile
+    pub global: GlobalOptions,
+
+    /// Defaults for `caddy-dev generate`
+    pub generate: GenerateDefaults,
+
+    /// Variables shared by every template, overridden by vars files and `--var`
+    pub vars: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            caddy_bin: "caddy".to_string(),
+            admin: DEFAULT_ADMIN.to_string(),
+            reload: ReloadMethod::default(),
+            folders: Vec::new(),
+            global: GlobalOptions::default(),
+            generate: GenerateDefaults::default(),
+            vars: BTreeMap::new(),
+        }
+    }
+}
+
+/// How the configuration is applied to a running Caddy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadMethod {
+    /// Adapt and load the Caddyfile through the admin API (no caddy binary needed)
+    #[default]
+    Api,
+    /// Run `caddy reload`
+    Cli,
+}
+
+/// Caddy global options managed by caddy-dev. The `admin` option comes from [`Config::admin`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlobalOptions {
+    /// Enable debug logging
+    pub debug: bool,
+
+    /// Issue all certificates from Caddy's internal CA
+    pub local_certs: bool,
+
+    /// Automatic HTTPS mode (`off`, `disable_redirects`, `disable_certs`, `ignore_loaded_certs`)
+    pub auto_https: Option<AutoHttps>,
+
+    /// Port for HTTP
+    pub http_port: Option<u16>,
+
+    /// Port for HTTPS
+    pub https_port: Option<u16>,
+
+    /// Additional lines copied verbatim into the global options block
+    pub extra: Vec<String>,
+}
+
+/// Values of the `auto_https` global option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoHttps {
+    Off,
+    DisableRedirects,
+    DisableCerts,
+    IgnoreLoadedCerts,
+}
+
+impl AutoHttps {
+    fn as_str(self) -> &'static str {
+        match self {
+            AutoHttps::Off => "off",
+            AutoHttps::DisableRedirects => "disable_redirects",
+            AutoHttps::DisableCerts => "disable_certs",
+            AutoHttps::IgnoreLoadedCerts => "ignore_loaded_certs",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(AutoHttps::Off),
+            "disable_redirects" => Some(AutoHttps::DisableRedirects),
+            "disable_certs" => Some(AutoHttps::DisableCerts),
+            "ignore_loaded_certs" => Some(AutoHttps::IgnoreLoadedCerts),
+            _ => None,
+        }
+    }
+}
+
+impl GlobalOptions {
+    /// Render the global options block, including the admin endpoint
+    fn render(&self, admin: &str) -> String {
+        let mut lines = vec![format!("admin {}", admin)];
+        if self.debug {
+            lines.push("debug".to_string());
+        }
+        if self.local_certs {
+            lines.push("local_certs".to_string());
+        }
+        if let Some(auto_https) = self.auto_https {
+            lines.push(format!("auto_https {}", auto_https.as_str()));
+        }
+        if let Some(port) = self.http_port {
+            lines.push(format!("http_port {}", port));
+        }
+        if let Some(port) = self.https_port {
+            lines.push(format!("https_port {}", port));
+        }
+        lines.extend(self.extra.iter().cloned());
+
+        let mut block = String::from("{\n");
+        for line in lines {
+            block.push('\t');
+            block.push_str(&line);
+            block.push('\n');
+        }
+        block.push_str("}\n");
+        block
+    }
+}
+
+/// Defaults for `caddy-dev generate`, overridden by its command-line flags
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerateDefaults {
+    /// Template file name, relative to the output directory
+    pub template: String,
+
+    /// Variables file name, relative to the output directory
+    pub vars_file: String,
+
+    /// Always load `.env` and `.env.local` (like `--dotenv`)
+    pub dotenv: bool,
+
+    /// Always keep unresolved placeholders (like `--allow-missing`)
+    pub allow_missing: bool,
+}
+
+impl Default for GenerateDefaults {
+    fn default() -> Self {
+        Self {
+            template: "Caddyfile.template".to_string(),
+            vars_file: VARS_FILE_NAME.to_string(),
+            dotenv: false,
+            allow_missing: false,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration, returning `None` if the file doesn't exist
+    pub fn load(path: &Path) -> Result<Option<Self>, CaddyDevError> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                toml::from_str(&content)
+                    .map(Some)
+                    .map_err(|e| CaddyDevError::ConfigInvalid {
+                        path: path.to_path_buf(),
+                        message: e.to_string(),
+                    })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(CaddyDevError::Io {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    /// Recover the configuration from a main Caddyfile written by older versions of
+    /// caddy-dev, which only recorded folders as `# Pattern:` comments. A hand-written
+    /// global options block is kept as well.
+    pub fn from_legacy_caddyfile(content: &str) -> Self {
+        let mut config = Self {
+            folders: content
+                .lines()
+                .filter_map(|line| line.strip_prefix("# Pattern:"))
+                .map(|folder| folder.trim().to_string())
+                .filter(|folder| !folder.is_empty())
+                .collect(),
+            ..Self::default()
+        };
+
+        // The global options block is the first non-comment content of the file
+        let mut lines = content.lines().filter(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        });
+        if lines.next().map(str::trim) != Some("{") {
+            return config;
+        }
+        let mut depth = 1;
+        for line in lines {
+            let trimmed = line.trim();
+            let top_level = depth == 1;
+            depth += trimmed.matches('{').count();
+            depth -= trimmed.matches('}').count().min(depth);
+            if depth == 0 {
+                break;
+            }
+            if !(top_level && config.parse_global_option(trimmed)) {
+                // Keep the indentation of nested blocks, relative to the options block
+                let line = line.trim_end();
+                let line = line
+                    .strip_prefix('\t')
+                    .or_else(|| line.strip_prefix("    "))
+                    .unwrap_or(trimmed);
+                config.global.extra.push(line.to_string());
+            }
+        }
+        config
+    }
+
+    /// Apply a single-line global option that caddy-dev manages, returning `false` otherwise
+    fn parse_global_option(&mut self, line: &str) -> bool {
+        let mut parts = line.split_whitespace();
+        let (Some(name), value, None) = (parts.next(), parts.next(), parts.next()) else {
+            return false;
+        };
+        let global = &mut self.global;
+        match (name, value) {
+            ("admin", Some(admin)) => self.admin = admin.to_string(),
+            ("debug", None) => global.debug = true,
+            ("local_certs", None) => global.local_certs = true,
+            ("auto_https", Some(mode)) => match AutoHttps::parse(mode) {
+                Some(mode) => global.auto_https = Some(mode),
+                None => return false,
+            },
+            ("http_port", Some(port)) => match port.parse() {
+                Ok(port) => global.http_port = Some(port),
+                Err(_) => return false,
+            },
+            ("https_port", Some(port)) => match port.parse() {
+                Ok(port) => global.https_port = Some(port),
+                Err(_) => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Serialize the configuration as TOML
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// Write the configuration to disk
+    pub fn save(&self, path: &Path) -> Result<(), CaddyDevError> {
+        let content = self
+            .to_toml()
+            .map_err(|message| CaddyDevError::ConfigInvalid {
+                path: path.to_path_buf(),
+                message,
+            })?;
+        fs::write(
+            path,
+            format!(
+                "# caddy-dev configuration\n# The main Caddyfile is generated from this file\n\n{}",
+                content
+            ),
+        )
+        .map_err(|source| CaddyDevError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Add a folder, returning `false` if it was already configured
+    pub fn add_folder(&mut self, folder: String) -> bool {
+        if self.folders.contains(&folder) {
+            return false;
+        }
+        self.folders.push(folder);
+        true
+    }
+
+    /// Remove a folder, returning `false` if it wasn't configured
+    pub fn remove_folder(&mut self, folder: &str) -> bool {
+        let before = self.folders.len();
+        self.folders.retain(|f| f != folder);
+        self.folders.len() != before
+    }
+
+    /// Caddyfile.dev files currently matched by the configured folders, in import order
+    pub fn imported_files(&self) -> Vec<ImportedFile> {
+        let mut files: Vec<ImportedFile> = Vec::new();
+        for folder in &self.folders {
+            for path in glob_paths(&import_pattern(folder))
+                .into_iter()
+                .filter(|path| path.is_file())
+            {
+                if !files.iter().any(|file| file.path == path) {
+                    files.push(ImportedFile {
+                        folder: folder.clone(),
+                        path,
+                    });
+                }
+            }
+        }
+        files
+    }
+
+    /// Files that Caddy would import more than once because several folders match them.
+    /// Once a `**` pattern makes every folder import file by file, see
+    /// [`Config::render_caddyfile`], each file is imported once and nothing overlaps.
+    pub fn overlapping_files(&self) -> Vec<Overlap> {
+        if self.has_recursive_folders() {
+            return Vec::new();
+        }
+        let mut matches: Vec<Overlap> = Vec::new();
+        for folder in &self.folders {
+            for path in glob_paths(&import_pattern(folder))
+                .into_iter()
+                .filter(|path| path.is_file())
+            {
+                match matches.iter_mut().find(|overlap| overlap.path == path) {
+                    Some(overlap) => overlap.folders.push(folder.clone()),
+                    None => matches.push(Overlap {
+                        path,
+                        folders: vec![folder.clone()],
+                    }),
+                }
+            }
+        }
+        matches.retain(|overlap| overlap.folders.len() > 1);
+        matches
+    }
+
+    /// Directories where the configured folders look for a Caddyfile.dev, whether it
+    /// exists yet or not, in import order
+    pub fn project_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for folder in &self.folders {
+            let pattern = import_pattern(folder);
+            let Some(dir_pattern) = Path::new(&pattern).parent() else {
+                continue;
+            };
+            for dir in glob_paths(&dir_pattern.to_string_lossy()) {
+                if dir.is_dir() && !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+        dirs
+    }
+
+    /// Header comment and global options shared by every generated Caddyfile
+    fn render_header(&self) -> String {
+        let mut caddyfile_content = String::new();
+        caddyfile_content.push_str("# Auto-generated by caddy-dev from config.toml\n");
+        caddyfile_content.push_str(
+            "# Do not edit: run 'caddy-dev folders' or 'caddy-dev init' to reconfigure\n\n",
+        );
+
+        // Global options from the [global] section
+        caddyfile_content.push_str(&self.global.render(&self.admin));
+        caddyfile_content.push('\n');
+        caddyfile_content
+    }
+
+    /// Render a main Caddyfile importing the given files one by one instead of
+    /// through glob patterns, listing the `skipped` files in a comment
+    pub fn render_caddyfile_with_files(
+        &self,
+        files: &[ImportedFile],
+        skipped: &[PathBuf],
+    ) -> String {
+        let mut caddyfile_content = self.render_header();
+
+        if !skipped.is_empty() {
+            caddyfile_content.push_str("# Skipped broken files:\n");
+            for path in skipped {
+                caddyfile_content.push_str(&format!("#   {}\n", path.display()));
+            }
+            caddyfile_content.push('\n');
+        }
+
+        caddyfile_content.push_str("# Import Caddyfile.dev files from configured folders\n");
+        let mut current_folder = None;
+        for file in files {
+            if current_folder != Some(&file.folder) {
+                caddyfile_content.push_str(&format!("# Pattern: {}\n", file.folder));
+                current_folder = Some(&file.folder);
+            }
+            caddyfile_content.push_str(&format!("import \"{}\"\n", file.path.display()));
+        }
+
+        caddyfile_content
+    }
+
+    /// Whether a configured folder uses a `**` pattern, expanded by caddy-dev
+    pub fn has_recursive_folders(&self) -> bool {
+        self.folders
+            .iter()
+            .any(|folder| is_recursive(&import_pattern(folder)))
+    }
+
+    /// Render the main Caddyfile. Folders with `**` patterns are imported file by file,
+    /// as matched right now, since Caddy's import doesn't support recursive globs. Every
+    /// other folder is then imported file by file too, so that a file matched by both a
+    /// `**` pattern and another folder is imported only once.
+    pub fn render_caddyfile(&self) -> String {
+        let mut caddyfile_content = self.render_header();
+        let files = self.has_recursive_folders().then(|| self.imported_files());
+
+        // Process each folder/pattern and add imports
+        caddyfile_content.push_str("# Import Caddyfile.dev files from configured folders\n");
+
+        for folder in &self.folders {
+            caddyfile_content.push_str(&format!("# Pattern: {}\n", folder));
+            let Some(files) = &files else {
+                caddyfile_content.push_str(&format!("import {}\n", import_pattern(folder)));
+                continue;
+            };
+            let mut matched = files
+                .iter()
+                .filter(|file| &file.folder == folder)
+                .peekable();
+            if matched.peek().is_none() {
+                caddyfile_content.push_str(&unmatched_comment(folder, files));
+            }
+            for file in matched {
+                caddyfile_content.push_str(&format!("import \"{}\"\n", file.path.display()));
+            }
+        }
+
+        caddyfile_content
+    }
+}
+
+/// Comment for a folder left without imports when importing file by file: its files are
+/// either all imported by earlier folders, or don't exist yet
+fn unmatched_comment(folder: &str, files: &[ImportedFile]) -> String {
+    let mut claimed_by: Vec<String> = Vec::new();
+    for path in glob_paths(&import_pattern(folder)) {
+        let owner = files.iter().find(|file| file.path == path);
+        if let Some(owner) = owner.filter(|owner| !claimed_by.contains(&owner.folder)) {
+            claimed_by.push(owner.folder.clone());
+        }
+    }
+    if claimed_by.is_empty() {
+        return "# No file matches yet\n".to_string();
+    }
+    let folders: Vec<String> = claimed_by.iter().map(|f| format!("'{}'", f)).collect();
+    format!("# Already imported by {}\n", folders.join(", "))
+}
+
+/// Import pattern for a configured folder, glob pattern or path to a Caddyfile.dev.
+/// Patterns with `**` can't be given to Caddy as-is, see [`is_recursive`].
+pub fn import_pattern(folder: &str) -> String {
+    let clean_folder = folder.trim_end_matches('/');
+
+    // Check if it's a glob pattern (contains * or ?) or a single project's file
+    if clean_folder.contains('*')
+        || clean_folder.contains('?')
+        || Path::new(clean_folder).file_name() == Some(OUTPUT_FILE_NAME.as_ref())
+    {
+        // It's a glob pattern - use it directly
+        clean_folder.to_string()
+    } else {
+        // It's a directory path - clean trailing slashes and generate glob pattern
+        // One level deep, like projects in ~/Developer; nested packages need a ** pattern
+        format!("{}/*/Caddyfile.dev", clean_folder)
+    }
+}
+
+/// Whether a pattern matches recursively with `**`. Caddy's import only supports
+/// single-level wildcards, so caddy-dev expands these patterns itself.
+pub fn is_recursive(pattern: &str) -> bool {
+    pattern.contains("**")
+}
+
+/// Options for matching paths against a folder pattern like Caddy's import does: `*`
+/// stays within one directory. Recursive patterns don't descend into hidden
+/// directories like `.git`.
+pub fn match_options(pattern: &str) -> glob::MatchOptions {
+    glob::MatchOptions {
+        require_literal_separator: true,
+        require_literal_leading_dot: is_recursive(pattern),
+        ..glob::MatchOptions::new()
+    }
+}
+
+/// Paths matching a glob pattern, in order, see [`match_options`]
+pub fn glob_paths(pattern: &str) -> Vec<PathBuf> {
+    glob::glob_with(pattern, match_options(pattern))
+        .map(|paths| paths.flatten().collect())
+        .unwrap_or_default()
+}
+
+/// Expand a leading `~` to the home directory
+fn expand_home(input: &str) -> String {
+    let rest = match input.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest.trim_start_matches('/'),
+        _ => return input.to_string(),
+    };
+    match dirs::home_dir() {
+        Some(home) => home.join(rest).to_string_lossy().into_owned(),
+        None => input.to_string(),
+    }
+}
+
+/// Normalize a folder entered by the user: expand `~`, make plain paths absolute
+/// and strip trailing slashes
+pub fn normalize_folder(input: &str) -> String {
+    let expanded = expand_home(input.trim());
+    let is_pattern = expanded.contains('*') || expanded.contains('?');
+    let folder = match std::env::current_dir() {
+        Ok(cwd) if !is_pattern && PathBuf::from(&expanded).is_relative() => {
+            cwd.join(&expanded).to_string_lossy().into_owned()
+        }
+        _ => expanded,
+    };
+    let trimmed = folder.trim_end_matches('/');
+    if trimmed.is_empty() {
+        folder
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Leading components of a glob pattern that contain no wildcards
+pub fn pattern_base(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory tree under the system temp dir with a Caddyfile.dev in each of `projects`
+    fn projects_root(name: &str, projects: &[&str]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("caddy-dev-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for project in projects {
+            fs::create_dir_all(root.join(project)).unwrap();
+            fs::write(root.join(project).join("Caddyfile.dev"), "").unwrap();
+        }
+        root
+    }
+
+    fn imports(caddyfile: &str) -> Vec<&str> {
+        caddyfile
+            .lines()
+            .filter(|line| line.starts_with("import "))
+            .collect()
+    }
+
+    #[test]
+    fn plain_folders_are_imported_with_a_glob() {
+        let config = Config {
+            folders: vec![
+                "/dev".to_string(),
+                "/work/*/app/Caddyfile.dev".to_string(),
+                "/home/me/app/Caddyfile.dev".to_string(),
+            ],
+            ..Config::default()
+        };
+        assert_eq!(
+            imports(&config.render_caddyfile()),
+            [
+                "import /dev/*/Caddyfile.dev",
+                "import /work/*/app/Caddyfile.dev",
+                "import /home/me/app/Caddyfile.dev"
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_folders_import_each_file_once() {
+        let root = projects_root("overlap", &["a", "b/pkg/web", ".git/x"]);
+        let dev = root.to_string_lossy();
+        let config = Config {
+            folders: vec![format!("{}/**/Caddyfile.dev", dev), dev.to_string()],
+            ..Config::default()
+        };
+
+        let caddyfile = config.render_caddyfile();
+
+        assert_eq!(
+            imports(&caddyfile),
+            [
+                format!("import \"{}/a/Caddyfile.dev\"", dev),
+                format!("import \"{}/b/pkg/web/Caddyfile.dev\"", dev),
+            ]
+        );
+        assert!(caddyfile.ends_with(&format!(
+            "# Pattern: {}\n# Already imported by '{}/**/Caddyfile.dev'\n",
+            dev, dev
+        )));
+        assert!(config.overlapping_files().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn folders_without_files_are_marked_when_importing_file_by_file() {
+        let root = projects_root("no-match", &["a/web"]);
+        let dev = root.to_string_lossy();
+        let config = Config {
+            folders: vec![
+                format!("{}/**/Caddyfile.dev", dev),
+                format!("{}/missing", dev),
+            ],
+            ..Config::default()
+        };
+
+        let caddyfile = config.render_caddyfile();
+
+        assert_eq!(
+            imports(&caddyfile),
+            [format!("import \"{}/a/web/Caddyfile.dev\"", dev)]
+        );
+        assert!(caddyfile.ends_with(&format!(
+            "# Pattern: {}/missing\n# No file matches yet\n",
+            dev
+        )));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn overlapping_plain_folders_are_reported() {
+        let root = projects_root("overlap-plain", &["a", "b"]);
+        let dev = root.to_string_lossy();
+        let config = Config {
+            folders: vec![
+                dev.to_string(),
+                format!("{}/a*/Caddyfile.dev", dev),
+                format!("{}/*/Caddyfile.dev", dev),
+            ],
+            ..Config::default()
+        };
+
+        assert_eq!(
+            config.overlapping_files(),
+            [
+                Overlap {
+                    path: root.join("a/Caddyfile.dev"),
+                    folders: config.folders.clone(),
+                },
+                Overlap {
+                    path: root.join("b/Caddyfile.dev"),
+                    folders: vec![config.folders[0].clone(), config.folders[2].clone()],
+                },
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn global_options_render_managed_options_then_extra_lines() {
+        let global = GlobalOptions {
+            debug: true,
+            local_certs: true,
+            auto_https: Some(AutoHttps::DisableRedirects),
+            http_port: Some(8080),
+            https_port: Some(8443),
+            extra: vec![
+                "email me@example.com".to_string(),
+                "servers {".to_string(),
+                "\tprotocols h1 h2".to_string(),
+                "}".to_string(),
+            ],
+        };
+        assert_eq!(
+            global.render("localhost:2019"),
+            "{\n\tadmin localhost:2019\n\tdebug\n\tlocal_certs\n\tauto_https disable_redirects\n\
+             \thttp_port 8080\n\thttps_port 8443\n\temail me@example.com\n\tservers {\n\
+             \t\tprotocols h1 h2\n\t}\n}\n"
+        );
+        assert_eq!(
+            GlobalOptions::default().render(":2019"),
+            "{\n\tadmin :2019\n}\n"
+        );
+    }
+
+    #[test]
+    fn legacy_caddyfile_keeps_folders_and_global_options() {
+        let legacy = "\
+# Caddy-dev main Caddyfile
+
+{
+    admin localhost:2020
+    debug
+    auto_https prefer_wildcard
+    email me@example.com
+    servers {
+        protocols h1 h2
+        timeouts {
+            read_body 10s
+        }
+    }
+    http_port 8080
+}
+
+# Pattern: /home/me/dev
+import /home/me/dev/*/Caddyfile.dev
+# Pattern: /home/me/work/*/Caddyfile.dev
+import /home/me/work/*/Caddyfile.dev
+";
+        let config = Config::from_legacy_caddyfile(legacy);
+
+        assert_eq!(
+            config.folders,
+            ["/home/me/dev", "/home/me/work/*/Caddyfile.dev"]
+        );
+        assert_eq!(config.admin, "localhost:2020");
+        assert!(config.global.debug);
+        assert!(!config.global.local_certs);
+        assert_eq!(config.global.http_port, Some(8080));
+        // Unknown values of managed options are kept verbatim
+        assert_eq!(config.global.auto_https, None);
+        assert_eq!(
+            config.global.extra,
+            [
+                "auto_https prefer_wildcard",
+                "email me@example.com",
+                "servers {",
+                "    protocols h1 h2",
+                "    timeouts {",
+                "        read_body 10s",
+                "    }",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_caddyfile_without_global_options_uses_defaults() {
+        let config =
+            Config::from_legacy_caddyfile("# Pattern: /dev\nimport /dev/*/Caddyfile.dev\n");
+        assert_eq!(config.folders, ["/dev"]);
+        assert_eq!(config.admin, DEFAULT_ADMIN);
+        assert!(config.global.extra.is_empty());
+    }
+
+    #[test]
+    fn rendered_caddyfile_round_trips_through_the_legacy_reader() {
+        let config = Config {
+            admin: "localhost:2999".to_string(),
+            folders: vec!["/dev".to_string(), "/work/*/Caddyfile.dev".to_string()],
+            global: GlobalOptions {
+                local_certs: true,
+                auto_https: Some(AutoHttps::Off),
+                https_port: Some(8443),
+                extra: vec![
+                    "log {".to_string(),
+                    "\toutput file /tmp/caddy.log".to_string(),
+                    "}".to_string(),
+                ],
+                ..GlobalOptions::default()
+            },
+            ..Config::default()
+        };
+
+        let caddyfile = config.render_caddyfile();
+        let recovered = Config::from_legacy_caddyfile(&caddyfile);
+
+        assert_eq!(recovered.folders, config.folders);
+        assert_eq!(recovered.admin, config.admin);
+        assert_eq!(recovered.global.extra, config.global.extra);
+        assert_eq!(recovered.render_caddyfile(), caddyfile);
+    }
+
+    #[test]
+    fn explicit_imports_list_skipped_files_and_group_by_folder() {
+        let config = Config {
+            folders: vec!["/dev".to_string(), "/work/*/Caddyfile.dev".to_string()],
+            ..Config::default()
+        };
+        let file = |folder: &str, path: &str| ImportedFile {
+            folder: folder.to_string(),
+            path: PathBuf::from(path),
+        };
+        let files = [
+            file("/dev", "/dev/a/Caddyfile.dev"),
+            file("/dev", "/dev/c/Caddyfile.dev"),
+            file("/work/*/Caddyfile.dev", "/work/x/Caddyfile.dev"),
+        ];
+        let skipped = [PathBuf::from("/dev/b/Caddyfile.dev")];
+
+        let caddyfile = config.render_caddyfile_with_files(&files, &skipped);
+
+        assert!(caddyfile.starts_with(&config.render_header()));
+        assert!(caddyfile.ends_with(
+            "\
+# Skipped broken files:
+#   /dev/b/Caddyfile.dev
+
+# Import Caddyfile.dev files from configured folders
+# Pattern: /dev
+import \"/dev/a/Caddyfile.dev\"
+import \"/dev/c/Caddyfile.dev\"
+# Pattern: /work/*/Caddyfile.dev
+import \"/work/x/Caddyfile.dev\"
+"
+        ));
+        // Folders are still recovered from the pattern comments
+        assert_eq!(
+            Config::from_legacy_caddyfile(&caddyfile).folders,
+            config.folders
+        );
+    }
+
+    #[test]
+    fn explicit_imports_without_skipped_files_have_no_skipped_section() {
+        let config = Config::default();
+        let files = [ImportedFile {
+            folder: "/dev".to_string(),
+            path: PathBuf::from("/dev/a/Caddyfile.dev"),
+        }];
+
+        let caddyfile = config.render_caddyfile_with_files(&files, &[]);
+
+        assert!(!caddyfile.contains("Skipped"));
+        assert_eq!(imports(&caddyfile), ["import \"/dev/a/Caddyfile.dev\""]);
+    }
+}
@@ -0,0 +1,325 @@
+// src/doctor.rs
+//! Diagnostics of the local setup for `caddy-dev doctor`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::admin::{AdminClient, AdminError};
+use crate::config::{Config, glob_paths, import_pattern, is_recursive, pattern_base};
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// A diagnostic, with a remediation hint unless it passed
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &str, message: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: Status::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// The config directory exists
+pub fn check_config_dir(config_dir: &Path) -> Check {
+    if config_dir.is_dir() {
+        Check::pass("config directory", config_dir.display().to_string())
+    } else {
+        Check::fail(
+            "config directory",
+            format!("'{}' does not exist", config_dir.display()),
+            "Run 'caddy-dev init' to set up caddy-dev",
+        )
+    }
+}
+
+/// `config.toml` exists and parses; returns the configuration to run the other checks with
+pub fn check_config_file(config_path: &Path, main_caddyfile_path: &Path) -> (Check, Config) {
+    match Config::load(config_path) {
+        Ok(Some(config)) => (
+            Check::pass("config.toml", config_path.display().to_string()),
+            config,
+        ),
+        Ok(None) => match fs::read_to_string(main_caddyfile_path) {
+            Ok(content) => (
+                Check::warn(
+                    "config.toml",
+                    "not found; settings are read from the main Caddyfile",
+                    "It's written by the next 'caddy-dev folders add' or 'caddy-dev folders remove'",
+                ),
+                Config::from_legacy_caddyfile(&content),
+            ),
+            Err(_) => (
+                Check::fail(
+                    "config.toml",
+                    format!("'{}' does not exist", config_path.display()),
+                    "Run 'caddy-dev init' to set up caddy-dev",
+                ),
+                Config::default(),
+            ),
+        },
+        Err(e) => (
+            Check::fail(
+                "config.toml",
+                e.to_string(),
+                format!("Fix the syntax of {}", config_path.display()),
+            ),
+            Config::default(),
+        ),
+    }
+}
+
+/// The main Caddyfile has been generated
+pub fn check_main_caddyfile(main_caddyfile_path: &Path) -> Check {
+    if main_caddyfile_path.is_file() {
+        Check::pass("main Caddyfile", main_caddyfile_path.display().to_string())
+    } else {
+        Check::fail(
+            "main Caddyfile",
+            format!("'{}' does not exist", main_caddyfile_path.display()),
+            "Run 'caddy-dev init' to set up caddy-dev",
+        )
+    }
+}
+
+/// The caddy binary can be executed
+pub fn check_caddy_binary(caddy_bin: &str, config_path: &Path) -> Check {
+    let hint = format!(
+        "Install Caddy (https://caddyserver.com/docs/install) or set 'caddy_bin' in {}",
+        config_path.display()
+    );
+    match Command::new(caddy_bin).arg("version").output() {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let version = stdout
+                .split_whitespace()
+                .next()
+                .unwrap_or("unknown version");
+            Check::pass("caddy", format!("{} ({})", version, caddy_bin))
+        }
+        Ok(output) => Check::fail(
+            "caddy",
+            format!("'{} version' failed with {}", caddy_bin, output.status),
+            hint,
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Check::fail("caddy", format!("'{}' not found on PATH", caddy_bin), hint)
+        }
+        Err(e) => Check::fail(
+            "caddy",
+            format!("cannot execute '{}': {}", caddy_bin, e),
+            hint,
+        ),
+    }
+}
+
+/// The admin endpoint is Caddy's, or at least free for Caddy to use
+pub fn check_admin_endpoint(admin: &str, config_path: &Path) -> Check {
+    let taken_hint = format!(
+        "Stop the program using it, or set 'admin' in {} to a free address like localhost:2020",
+        config_path.display()
+    );
+    match AdminClient::new(admin).config() {
+        Ok(_) => Check::pass(
+            "admin endpoint",
+            format!("Caddy is answering at '{}'", admin),
+        ),
+        Err(AdminError::Connect { .. }) => Check::warn(
+            "admin endpoint",
+            format!("nothing is answering at '{}'; Caddy is not running", admin),
+            "Start it with 'caddy-dev start' or 'caddy-dev run'",
+        ),
+        Err(AdminError::Api { status, .. }) => Check::fail(
+            "admin endpoint",
+            format!(
+                "'{}' answers with HTTP {}; the port may be taken by another program",
+                admin, status
+            ),
+            taken_hint,
+        ),
+        Err(AdminError::Protocol(_)) => Check::fail(
+            "admin endpoint",
+            format!("'{}' is taken by something other than Caddy", admin),
+            taken_hint,
+        ),
+    }
+}
+
+/// Each configured folder exists, is importable by Caddy and matches Caddyfile.dev files
+pub fn check_folders(config: &Config) -> Vec<Check> {
+    if config.folders.is_empty() {
+        return vec![Check::warn(
+            "folders",
+            "no folders configured",
+            "Add one with 'caddy-dev folders add <path>'",
+        )];
+    }
+
+    let mut checks = Vec::new();
+    for folder in &config.folders {
+        let name = format!("folder '{}'", folder);
+        let pattern = import_pattern(folder);
+        let base = pattern_base(Path::new(&pattern));
+
+        if !base.is_dir() {
+            checks.push(Check::fail(
+                &name,
+                format!("'{}' does not exist", base.display()),
+                format!(
+                    "Create it, or remove it with 'caddy-dev folders remove {}'",
+                    folder
+                ),
+            ));
+        } else {
+            let matches = glob_paths(&pattern)
+                .iter()
+                .filter(|path| path.is_file())
+                .count();
+            if matches == 0 {
+                checks.push(Check::warn(
+                    &name,
+                    format!("no file matches '{}'", pattern),
+                    "Generate one with 'caddy-dev generate' in a project under it",
+                ));
+            } else if is_recursive(&pattern) {
+                checks.push(Check::pass(
+                    &name,
+                    format!("{} Caddyfile.dev file(s), imported one by one", matches),
+                ));
+            } else {
+                checks.push(Check::pass(
+                    &name,
+                    format!("{} Caddyfile.dev file(s)", matches),
+                ));
+            }
+        }
+    }
+    checks
+}
+
+/// Caddy's data directory, where it keeps its local CA (same rules as Caddy)
+fn caddy_data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("caddy"));
+    }
+    let name = if cfg!(any(windows, target_os = "macos")) {
+        "Caddy"
+    } else {
+        "caddy"
+    };
+    dirs::data_dir().map(|dir| dir.join(name))
+}
+
+/// Caddy's local root CA, used for `*.localhost` certificates, is trusted by the system
+pub fn check_local_ca() -> Check {
+    let trust_hint = "Run 'caddy trust' (it may ask for your password)";
+    let Some(root) = caddy_data_dir().map(|dir| dir.join("pki/authorities/local/root.crt")) else {
+        return Check::warn(
+            "local CA",
+            "cannot locate Caddy's data directory",
+            trust_hint,
+        );
+    };
+    let Ok(pem) = fs::read_to_string(&root) else {
+        return Check::warn(
+            "local CA",
+            format!("'{}' does not exist yet", root.display()),
+            "Caddy creates it when it first serves a site over HTTPS; then run 'caddy trust'",
+        );
+    };
+
+    match is_trusted(&root, &pem) {
+        Some(true) => Check::pass("local CA", format!("{} is trusted", root.display())),
+        Some(false) => Check::fail(
+            "local CA",
+            format!("{} is not trusted by the system", root.display()),
+            trust_hint,
+        ),
+        None => Check::warn(
+            "local CA",
+            "cannot check certificate trust on this platform",
+            trust_hint,
+        ),
+    }
+}
+
+/// Whether a root certificate is in the system trust store, if that can be checked
+#[cfg(target_os = "linux")]
+fn is_trusted(_root: &Path, pem: &str) -> Option<bool> {
+    const BUNDLES: [&str; 4] = [
+        "/etc/ssl/certs/ca-certificates.crt",
+        "/etc/pki/tls/certs/ca-bundle.crt",
+        "/etc/ssl/ca-bundle.pem",
+        "/etc/ssl/cert.pem",
+    ];
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(str::trim)
+        .collect();
+    if body.is_empty() {
+        return Some(false);
+    }
+    let trusted = BUNDLES.iter().any(|bundle| {
+        fs::read_to_string(bundle).is_ok_and(|content| {
+            content
+                .lines()
+                .map(str::trim)
+                .collect::<String>()
+                .contains(&body)
+        })
+    });
+    Some(trusted)
+}
+
+/// Whether a root certificate is in the system trust store, if that can be checked
+#[cfg(target_os = "macos")]
+fn is_trusted(root: &Path, _pem: &str) -> Option<bool> {
+    Command::new("security")
+        .arg("verify-cert")
+        .arg("-c")
+        .arg(root)
+        .output()
+        .ok()
+        .map(|output| output.status.success())
+}
+
+/// Whether a root certificate is in the system trust store, if that can be checked
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn is_trusted(_root: &Path, _pem: &str) -> Option<bool> {
+    None
+}
@@ -0,0 +1,254 @@
+// src/error.rs
+//! Errors returned by the caddy-dev library.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::Overlap;
+use crate::sites::{Duplicate, describe_duplicates};
+use crate::template::{TemplateError, Unresolved};
+
+/// Error returned by caddy-dev operations
+#[derive(Debug)]
+pub enum CaddyDevError {
+    /// A file or directory could not be read or written
+    Io { path: PathBuf, source: io::Error },
+    /// caddy-dev hasn't been initialized: the main Caddyfile doesn't exist
+    ConfigMissing { path: PathBuf },
+    /// `config.toml` could not be parsed or serialized
+    ConfigInvalid { path: PathBuf, message: String },
+    /// The directory to write Caddyfile.dev to doesn't exist
+    OutputDirNotFound { path: PathBuf },
+    /// The template could not be read
+    TemplateNotFound { path: PathBuf, source: io::Error },
+    /// The template has a syntax error
+    TemplateSyntax { path: PathBuf, error: TemplateError },
+    /// Placeholders of the template have no value
+    UnresolvedPlaceholder {
+        path: PathBuf,
+        placeholders: Vec<Unresolved>,
+        /// Warnings gathered before rendering, which may explain the missing values
+        warnings: Vec<String>,
+    },
+    /// A vars file or dotenv file could not be read or parsed
+    VarsFile { path: PathBuf, message: String },
+    /// The port registry could not be read or written
+    PortRegistry { path: PathBuf, message: String },
+    /// No port could be allocated to a name
+    PortUnavailable { name: String, message: String },
+    /// The caddy binary could not be executed
+    CaddyNotFound { bin: String, source: io::Error },
+    /// Caddy rejected the configuration; `report` points to the files that caused it
+    InvalidCaddyfile { report: String },
+    /// Imported files define the same site more than once
+    DuplicateSites(Vec<Duplicate>),
+    /// Files are imported more than once because several folders match them
+    OverlappingFolders(Vec<Overlap>),
+    /// Caddy's admin endpoint doesn't answer
+    CaddyNotRunning { address: String },
+    /// Caddy is already running, so it can't be started again
+    CaddyAlreadyRunning { address: String },
+    /// Caddy didn't accept the configuration while reloading
+    ReloadFailed { message: String },
+    /// Caddy exited while starting; `log` holds the last lines it wrote
+    StartFailed {
+        status: String,
+        log_path: PathBuf,
+        log: Vec<String>,
+    },
+    /// Caddy could not be stopped
+    StopFailed { message: String },
+    /// Files could not be watched for changes
+    Watch(io::Error),
+    /// Input is required, but there's no terminal to prompt for it
+    NonInteractive { message: String },
+    /// A configuration already exists and overwriting it wasn't allowed
+    ConfigExists { path: PathBuf },
+    /// Folders to remove aren't configured
+    FolderNotConfigured { folders: Vec<String> },
+    /// No port is allocated to a name in a project
+    PortNotAllocated { name: String, project: String },
+    /// A global variable isn't set
+    VarNotSet { name: String },
+    /// Upstreams probed by `health` don't answer
+    UpstreamsDown { down: usize, total: usize },
+    /// Checks run by `doctor` failed
+    ChecksFailed { failed: usize },
+    /// Projects could not be generated by `generate --all`
+    ProjectsFailed { failed: usize, total: usize },
+    /// Caddy exited on its own while running in the foreground
+    CaddyExited { status: String },
+    /// Standard input or output (`stream`) could not be read or written
+    Stdio {
+        stream: &'static str,
+        source: io::Error,
+    },
+}
+
+impl fmt::Display for CaddyDevError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaddyDevError::Io { path, source } => {
+                write!(f, "Cannot access '{}': {}", path.display(), source)
+            }
+            CaddyDevError::ConfigMissing { path } => {
+                write!(f, "Configuration file not found at '{}'", path.display())
+            }
+            CaddyDevError::ConfigInvalid { path, message } => {
+                write!(f, "Invalid configuration '{}': {}", path.display(), message)
+            }
+            CaddyDevError::OutputDirNotFound { path } => write!(
+                f,
+                "Output directory '{}' does not exist or is not a directory",
+                path.display()
+            ),
+            CaddyDevError::TemplateNotFound { path, source } => {
+                write!(f, "Cannot read template '{}': {}", path.display(), source)
+            }
+            CaddyDevError::TemplateSyntax { path, error } => write!(
+                f,
+                "Cannot parse template '{}:{}': {}",
+                path.display(),
+                error.position,
+                error.message
+            ),
+            CaddyDevError::UnresolvedPlaceholder {
+                path, placeholders, ..
+            } => {
+                let lines: Vec<String> = placeholders
+                    .iter()
+                    .map(|unresolved| describe_unresolved(path, unresolved))
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            CaddyDevError::VarsFile { path, message } => {
+                write!(
+                    f,
+                    "Cannot read variables from '{}': {}",
+                    path.display(),
+                    message
+                )
+            }
+            CaddyDevError::PortRegistry { path, message } => {
+                write!(f, "Port registry '{}': {}", path.display(), message)
+            }
+            CaddyDevError::PortUnavailable { name, message } => {
+                write!(f, "Cannot allocate a port for '{}': {}", name, message)
+            }
+            CaddyDevError::CaddyNotFound { bin, source } => {
+                write!(f, "Cannot execute '{}': {}", bin, source)
+            }
+            CaddyDevError::InvalidCaddyfile { report } => {
+                write!(f, "Configuration is invalid\n{}", report)
+            }
+            CaddyDevError::DuplicateSites(duplicates) => {
+                write!(f, "Duplicate sites\n{}", describe_duplicates(duplicates))
+            }
+            CaddyDevError::OverlappingFolders(overlaps) => {
+                write!(f, "Files imported more than once")?;
+                for overlap in overlaps {
+                    write!(
+                        f,
+                        "\n  {} (matched by {})",
+                        overlap.path.display(),
+                        overlap.folders.join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            CaddyDevError::CaddyNotRunning { address } => {
+                write!(f, "Caddy is not running at '{}'", address)
+            }
+            CaddyDevError::CaddyAlreadyRunning { address } => {
+                write!(f, "Caddy is already running at '{}'", address)
+            }
+            CaddyDevError::ReloadFailed { message } => {
+                write!(f, "Caddy reload failed\n{}", message)
+            }
+            CaddyDevError::StartFailed {
+                status,
+                log_path,
+                log,
+            } => {
+                write!(
+                    f,
+                    "Caddy exited with {}. Last lines of {}:",
+                    status,
+                    log_path.display()
+                )?;
+                for line in log {
+                    write!(f, "\n  {}", line)?;
+                }
+                Ok(())
+            }
+            CaddyDevError::StopFailed { message } => write!(f, "Cannot stop Caddy: {}", message),
+            CaddyDevError::Watch(source) => write!(f, "Cannot watch files: {}", source),
+            CaddyDevError::NonInteractive { message } => write!(f, "{}", message),
+            CaddyDevError::ConfigExists { path } => write!(
+                f,
+                "Refusing to overwrite the existing configuration at '{}'",
+                path.display()
+            ),
+            CaddyDevError::FolderNotConfigured { folders } => {
+                let folders: Vec<String> = folders.iter().map(|f| format!("'{}'", f)).collect();
+                write!(f, "Not a configured folder: {}", folders.join(", "))
+            }
+            CaddyDevError::PortNotAllocated { name, project } => {
+                write!(f, "No port allocated for '{}' in {}", name, project)
+            }
+            CaddyDevError::VarNotSet { name } => {
+                write!(f, "Global variable '{}' is not set", name)
+            }
+            CaddyDevError::UpstreamsDown { down, total } => {
+                write!(f, "{} of {} upstream(s) down", down, total)
+            }
+            CaddyDevError::ChecksFailed { failed } => write!(f, "{} check(s) failed", failed),
+            CaddyDevError::ProjectsFailed { failed, total } => {
+                write!(
+                    f,
+                    "{} of {} project(s) could not be generated",
+                    failed, total
+                )
+            }
+            CaddyDevError::CaddyExited { status } => write!(f, "Caddy exited with {}", status),
+            CaddyDevError::Stdio { stream, source } => {
+                write!(f, "Cannot access {}: {}", stream, source)
+            }
+        }
+    }
+}
+
+impl CaddyDevError {
+    /// Warnings that came up before the error, to report along with it
+    pub fn warnings(&self) -> &[String] {
+        match self {
+            CaddyDevError::UnresolvedPlaceholder { warnings, .. } => warnings,
+            _ => &[],
+        }
+    }
+}
+
+impl std::error::Error for CaddyDevError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaddyDevError::Io { source, .. }
+            | CaddyDevError::TemplateNotFound { source, .. }
+            | CaddyDevError::CaddyNotFound { source, .. }
+            | CaddyDevError::Stdio { source, .. }
+            | CaddyDevError::Watch(source) => Some(source),
+            CaddyDevError::TemplateSyntax { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// `file:line:column: unresolved placeholder '{{name}}'`
+pub fn describe_unresolved(path: &std::path::Path, unresolved: &Unresolved) -> String {
+    format!(
+        "{}:{}: unresolved placeholder '{{{{{}}}}}'",
+        path.display(),
+        unresolved.position,
+        unresolved.name
+    )
+}
@@ -0,0 +1,402 @@
+// src/generate.rs
+//! Rendering a project's template into its Caddyfile.dev.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{Config, ImportedFile};
+use crate::error::{CaddyDevError, describe_unresolved};
+use crate::template::Template;
+use crate::{git, ports, sites, store, vars};
+
+/// Name of the file written next to the template
+pub const OUTPUT_FILE_NAME: &str = "Caddyfile.dev";
+
+/// What to generate; unset options fall back to the `[generate]` section of config.toml
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    /// Directory to write Caddyfile.dev to (default: current directory)
+    pub output_dir: Option<PathBuf>,
+    /// Template file (default: `<output_dir>/Caddyfile.template`)
+    pub template: Option<PathBuf>,
+    /// Variables overriding the vars file
+    pub variables: Vec<(String, String)>,
+    /// Vars file (default: `<output_dir>/Caddyfile.vars` if present)
+    pub vars_file: Option<PathBuf>,
+    /// Load `.env` and `.env.local` from the output directory
+    pub dotenv: bool,
+    /// Additional dotenv files, later ones overriding earlier ones
+    pub env_files: Vec<PathBuf>,
+    /// Write Caddyfile.dev even if some placeholders have no value
+    pub allow_missing: bool,
+}
+
+/// A Caddyfile.dev written from a template
+#[derive(Debug, Clone)]
+pub struct Generated {
+    pub output_path: PathBuf,
+    pub vars_file: Option<PathBuf>,
+    pub env_paths: Vec<PathBuf>,
+    /// Variables of the template that had a value, sorted
+    pub applied: Vec<String>,
+    /// Ports allocated for the first time, as `(name, port)`
+    pub allocated_ports: Vec<(String, u16)>,
+    /// Problems that didn't prevent writing the file
+    pub warnings: Vec<String>,
+}
+
+/// Render a template into Caddyfile.dev
+pub fn generate(options: GenerateOptions, config: &Config) -> Result<Generated, CaddyDevError> {
+    let GenerateOptions {
+        output_dir,
+        template,
+        variables,
+        vars_file,
+        dotenv,
+        env_files,
+        allow_missing,
+    } = options;
+    let dotenv = dotenv || config.generate.dotenv;
+    let allow_missing = allow_missing || config.generate.allow_missing;
+    let mut warnings = Vec::new();
+
+    // Output directory (default: current)
+    let output_dir = output_dir.unwrap_or_else(|| PathBuf::from("."));
+    if !output_dir.is_dir() {
+        return Err(CaddyDevError::OutputDirNotFound { path: output_dir });
+    }
+
+    // Template path (default: output_dir/Caddyfile.template)
+    let template_path = template.unwrap_or_else(|| output_dir.join(&config.generate.template));
+
+    // Read template content
+    let template_content = match fs::read_to_string(&template_path) {
+        Ok(content) => content,
+        Err(source) => {
+            return Err(CaddyDevError::TemplateNotFound {
+                path: template_path,
+                source,
+            });
+        }
+    };
+
+    // Parse the template
+    let template = match Template::parse(&template_content) {
+        Ok(template) => template,
+        Err(error) => {
+            return Err(CaddyDevError::TemplateSyntax {
+                path: template_path,
+                error,
+            });
+        }
+    };
+
+    // Variables file (default: output_dir/Caddyfile.vars, skipped if absent)
+    let vars_file = match vars_file {
+        Some(path) => Some(path),
+        None => Some(output_dir.join(&config.generate.vars_file)).filter(|p| p.is_file()),
+    };
+    let file_vars = match &vars_file {
+        Some(path) => vars::read_vars_file(path)?,
+        None => Vec::new(),
+    };
+
+    // Collect project variables into a HashMap; --var entries override the vars file
+    let user_vars: HashMap<String, String> = file_vars.into_iter().chain(variables).collect();
+
+    // Dotenv files (--dotenv and --env-file), later files overriding earlier ones
+    let mut env_paths: Vec<PathBuf> = Vec::new();
+    if dotenv {
+        env_paths.extend(
+            vars::DOTENV_FILE_NAMES
+                .iter()
+                .map(|name| output_dir.join(name))
+                .filter(|p| p.is_file()),
+        );
+    }
+    env_paths.extend(env_files);
+    let mut env_vars: HashMap<String, String> = HashMap::new();
+    for path in &env_paths {
+        env_vars.extend(vars::read_dotenv(path)?);
+    }
+
+    // Resolve {{env.NAME}} placeholders; the process environment wins over dotenv files
+    let referenced = template.variables();
+    let mut vars: HashMap<String, String> = HashMap::new();
+    for name in &referenced {
+        if let Some(env_name) = name.strip_prefix(vars::ENV_PREFIX) {
+            let value = std::env::var(env_name)
+                .ok()
+                .or_else(|| env_vars.get(env_name).cloned());
+            if let Some(value) = value {
+                vars.insert(name.clone(), value);
+            }
+        }
+    }
+
+    // Built-in {{git.*}} variables, read from the repository containing the output directory
+    if referenced
+        .iter()
+        .any(|name| name.starts_with(git::GIT_PREFIX))
+    {
+        match git::GitInfo::discover(&output_dir) {
+            Some(info) => vars.extend(
+                info.variables()
+                    .into_iter()
+                    .filter(|(key, _)| referenced.contains(key)),
+            ),
+            None => warnings.push(format!(
+                "'{}' is not inside a Git repository; {{{{git.*}}}} variables are unavailable",
+                output_dir.display()
+            )),
+        }
+    }
+
+    // {{port:name}} placeholders, allocated once per project and name in the port registry
+    let port_names: Vec<&str> = referenced
+        .iter()
+        .filter_map(|name| name.strip_prefix(ports::PORT_PREFIX))
+        .collect();
+    let mut port_registry = None;
+    let mut allocated_ports = Vec::new();
+    if !port_names.is_empty() {
+        let mut registry = store::load_port_registry()?;
+        let project = ports::project_key(&output_dir);
+        for name in port_names {
+            let (port, allocated) = registry.allocate(&project, name)?;
+            if allocated {
+                allocated_ports.push((name.to_string(), port));
+            }
+            vars.insert(format!("{}{}", ports::PORT_PREFIX, name), port.to_string());
+        }
+        port_registry = Some(registry);
+    }
+    // Global variables from config.toml, overridden by project variables
+    vars.extend(
+        config
+            .vars
+            .iter()
+            .filter(|(key, _)| referenced.contains(*key))
+            .map(|(key, value)| (key.clone(), value.clone())),
+    );
+    vars.extend(user_vars.clone());
+
+    // Warn about project variables the template never references; global ones are
+    // shared by every template and not expected to be used by all of them
+    let mut unused: Vec<&String> = user_vars
+        .keys()
+        .filter(|k| !referenced.contains(*k))
+        .collect();
+    unused.sort();
+    for key in unused {
+        warnings.push(format!(
+            "variable '{}' is not used by template '{}'",
+            key,
+            template_path.display()
+        ));
+    }
+
+    // Render the template
+    let rendered = template.render(&vars);
+
+    // Report placeholders without a value
+    if !rendered.unresolved.is_empty() && !allow_missing {
+        return Err(CaddyDevError::UnresolvedPlaceholder {
+            path: template_path,
+            placeholders: rendered.unresolved,
+            warnings,
+        });
+    }
+    for unresolved in &rendered.unresolved {
+        warnings.push(describe_unresolved(&template_path, unresolved));
+    }
+
+    // Final output path
+    let output_path = output_dir.join(OUTPUT_FILE_NAME);
+
+    // Warn when another imported project already serves one of the generated sites
+    let own_path = fs::canonicalize(&output_dir)
+        .map(|dir| dir.join(OUTPUT_FILE_NAME))
+        .unwrap_or_else(|_| output_path.clone());
+    let others: Vec<ImportedFile> = config
+        .imported_files()
+        .into_iter()
+        .filter(|file| {
+            fs::canonicalize(&file.path).unwrap_or_else(|_| file.path.clone()) != own_path
+        })
+        .collect();
+    let (existing, _) = sites::collect_sites(&others);
+    for block in sites::parse_site_blocks(&rendered.output) {
+        for address in &block.addresses {
+            let key = sites::site_key(address);
+            for site in existing
+                .iter()
+                .filter(|site| sites::site_key(&site.address) == key)
+            {
+                warnings.push(format!(
+                    "site '{}' is also defined in {}:{}",
+                    address,
+                    site.file.display(),
+                    site.line
+                ));
+            }
+        }
+    }
+
+    // Write the result
+    if let Err(source) = fs::write(&output_path, rendered.output) {
+        return Err(CaddyDevError::Io {
+            path: output_path,
+            source,
+        });
+    }
+    if let Some(registry) = &port_registry {
+        registry.save()?;
+    }
+
+    Ok(Generated {
+        output_path,
+        vars_file,
+        env_paths,
+        applied: referenced
+            .into_iter()
+            .filter(|name| vars.contains_key(name))
+            .collect(),
+        allocated_ports,
+        warnings,
+    })
+}
+
+/// Outcome of generating one project with [`generate_all`]
+#[derive(Debug)]
+pub struct ProjectOutcome {
+    pub dir: PathBuf,
+    pub result: Result<Generated, CaddyDevError>,
+}
+
+/// Generate every project under the configured folders that has a template, each with
+/// its own vars file and dotenv files. `options` apply to every project, except for the
+/// per-project `output_dir`, `template` and `vars_file`, which are ignored.
+pub fn generate_all(options: &GenerateOptions, config: &Config) -> Vec<ProjectOutcome> {
+    config
+        .project_dirs()
+        .into_iter()
+        .filter(|dir| dir.join(&config.generate.template).is_file())
+        .map(|dir| {
+            let project_options = GenerateOptions {
+                output_dir: Some(dir.clone()),
+                template: None,
+                vars_file: None,
+                ..options.clone()
+            };
+            ProjectOutcome {
+                result: generate(project_options, config),
+                dir,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Empty directory under the system temp dir, unique to the test
+    fn project_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "caddy-dev-generate-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn options(dir: &Path, variables: &[(&str, &str)]) -> GenerateOptions {
+        GenerateOptions {
+            output_dir: Some(dir.to_path_buf()),
+            variables: variables
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..GenerateOptions::default()
+        }
+    }
+
+    #[test]
+    fn cli_vars_override_vars_file_which_overrides_global_vars() {
+        let dir = project_dir("precedence");
+        fs::write(
+            dir.join("Caddyfile.template"),
+            "{{global}} {{file}} {{cli}}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("Caddyfile.vars"),
+            "file=from-file\ncli=from-file\n",
+        )
+        .unwrap();
+        let mut config = Config::default();
+        for key in ["global", "file", "cli"] {
+            config
+                .vars
+                .insert(key.to_string(), "from-global".to_string());
+        }
+
+        generate(options(&dir, &[("cli", "from-cli")]), &config).unwrap();
+
+        let output = fs::read_to_string(dir.join(OUTPUT_FILE_NAME)).unwrap();
+        assert_eq!(output, "from-global from-file from-cli\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unused_global_vars_are_not_reported() {
+        let dir = project_dir("unused-global");
+        fs::write(dir.join("Caddyfile.template"), "{{used}}\n").unwrap();
+        let mut config = Config::default();
+        config.vars.insert("used".to_string(), "a".to_string());
+        config.vars.insert("other".to_string(), "b".to_string());
+
+        let generated = generate(options(&dir, &[("unused", "x")]), &config).unwrap();
+
+        assert_eq!(generated.warnings.len(), 1, "{:?}", generated.warnings);
+        assert!(generated.warnings[0].contains("'unused'"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unresolved_placeholders_keep_the_warnings_explaining_them() {
+        let dir = project_dir("outside-git");
+        fs::write(dir.join("Caddyfile.template"), "{{git.branch}}\n").unwrap();
+
+        let error = generate(options(&dir, &[]), &Config::default()).unwrap_err();
+
+        assert!(matches!(error, CaddyDevError::UnresolvedPlaceholder { .. }));
+        assert_eq!(error.warnings().len(), 1, "{:?}", error.warnings());
+        assert!(error.warnings()[0].contains("not inside a Git repository"));
+        assert!(!dir.join(OUTPUT_FILE_NAME).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_referenced_variables_are_applied() {
+        let dir = project_dir("applied");
+        fs::write(dir.join("Caddyfile.template"), "{{b}} {{a}} {{missing}}\n").unwrap();
+
+        let generated = generate(
+            GenerateOptions {
+                allow_missing: true,
+                ..options(&dir, &[("a", "1"), ("b", "2"), ("unused", "3")])
+            },
+            &Config::default(),
+        )
+        .unwrap();
+
+        assert_eq!(generated.applied, ["a", "b"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+// src/git.rs
+//! Git repository information for the built-in `{{git.*}}` variables.
+//!
+//! Reads `.git`, `HEAD`, `commondir` and ref files directly so no git binary is required.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::template::slugify;
+
+/// Prefix of the built-in git variables (`{{git.branch}}`)
+pub const GIT_PREFIX: &str = "git.";
+
+/// Information about the repository/worktree containing a directory
+#[derive(Debug, Clone)]
+pub struct GitInfo {
+    /// Checked out branch, `None` when HEAD is detached
+    pub branch: Option<String>,
+    /// Full commit hash of HEAD, `None` on an unborn branch
+    pub sha: Option<String>,
+    /// Name of the worktree directory
+    pub worktree_name: String,
+    /// Name of the main repository directory (shared by all its worktrees)
+    pub repo_name: String,
+}
+
+impl GitInfo {
+    /// Find the repository containing `start` by walking up to the nearest `.git`
+    pub fn discover(start: &Path) -> Option<Self> {
+        let start = start.canonicalize().ok()?;
+        let worktree_root = start.ancestors().find(|dir| dir.join(".git").exists())?;
+        let dot_git = worktree_root.join(".git");
+
+        // Linked worktrees and submodules have a `.git` file pointing to the real git dir
+        let git_dir = if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            let target = content.trim().strip_prefix("gitdir:")?.trim();
+            worktree_root.join(target)
+        } else {
+            dot_git
+        };
+
+        // Linked worktrees share refs with the main repository through `commondir`
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(content) => git_dir.join(content.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        let common_dir = common_dir.canonicalize().unwrap_or(common_dir);
+
+        let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        let (branch, sha) = match head.strip_prefix("ref:") {
+            Some(reference) => {
+                let reference = reference.trim();
+                let branch = reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string();
+                (Some(branch), resolve_ref(&common_dir, reference))
+            }
+            None => (None, Some(head.to_string())),
+        };
+
+        let worktree_name = dir_name(worktree_root);
+        let repo_name = repo_name(&common_dir).unwrap_or_else(|| worktree_name.clone());
+
+        Some(Self {
+            branch,
+            sha,
+            worktree_name,
+            repo_name,
+        })
+    }
+
+    /// Built-in template variables, keyed with the `git.` prefix
+    pub fn variables(&self) -> Vec<(String, String)> {
+        let mut vars = vec![
+            ("worktree_name", self.worktree_name.clone()),
+            ("repo_name", self.repo_name.clone()),
+        ];
+        if let Some(branch) = &self.branch {
+            vars.push(("branch", branch.clone()));
+            vars.push(("branch_slug", slugify(branch)));
+        }
+        if let Some(sha) = &self.sha {
+            vars.push(("sha", sha.clone()));
+            vars.push(("short_sha", sha.chars().take(7).collect()));
+        }
+        vars.into_iter()
+            .map(|(key, value)| (format!("{}{}", GIT_PREFIX, key), value))
+            .collect()
+    }
+}
+
+/// Resolve a ref like `refs/heads/main` from loose ref files or `packed-refs`
+fn resolve_ref(common_dir: &Path, reference: &str) -> Option<String> {
+    if let Ok(content) = fs::read_to_string(common_dir.join(reference)) {
+        return Some(content.trim().to_string());
+    }
+    let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .find_map(|line| {
+            let (sha, name) = line.split_once(' ')?;
+            (name.trim() == reference).then(|| sha.to_string())
+        })
+}
+
+/// Name of the repository owning `common_dir` (`/src/myapp/.git` or bare `/src/myapp.git` → `myapp`)
+fn repo_name(common_dir: &Path) -> Option<String> {
+    let dir: PathBuf = if common_dir.file_name()? == ".git" {
+        common_dir.parent()?.to_path_buf()
+    } else {
+        common_dir.to_path_buf()
+    };
+    let name = dir_name(&dir);
+    Some(name.strip_suffix(".git").unwrap_or(&name).to_string())
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+    const OTHER_SHA: &str = "89abcdef0123456789abcdef0123456789abcdef";
+
+    /// Empty directory under the system temp dir, unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("caddy-dev-git-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: PathBuf, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn variables(dir: &Path) -> HashMap<String, String> {
+        GitInfo::discover(dir)
+            .unwrap()
+            .variables()
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn reads_branch_and_sha_from_loose_refs() {
+        let root = temp_dir("loose");
+        let git_dir = root.join("myapp/.git");
+        write(git_dir.join("HEAD"), "ref: refs/heads/Feature/Login_Page\n");
+        write(
+            git_dir.join("refs/heads/Feature/Login_Page"),
+            &format!("{}\n", SHA),
+        );
+        fs::create_dir_all(root.join("myapp/web/src")).unwrap();
+
+        let vars = variables(&root.join("myapp/web/src"));
+
+        assert_eq!(vars["git.branch"], "Feature/Login_Page");
+        assert_eq!(vars["git.branch_slug"], "feature-login-page");
+        assert_eq!(vars["git.sha"], SHA);
+        assert_eq!(vars["git.short_sha"], "0123456");
+        assert_eq!(vars["git.worktree_name"], "myapp");
+        assert_eq!(vars["git.repo_name"], "myapp");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn falls_back_to_packed_refs() {
+        let root = temp_dir("packed");
+        let git_dir = root.join("myapp/.git");
+        write(git_dir.join("HEAD"), "ref: refs/heads/main\n");
+        write(
+            git_dir.join("packed-refs"),
+            &format!(
+                "# pack-refs with: peeled fully-peeled sorted\n\
+                 {} refs/heads/dev\n\
+                 {} refs/heads/main\n\
+                 ^{}\n",
+                OTHER_SHA, SHA, OTHER_SHA
+            ),
+        );
+
+        let vars = variables(&root.join("myapp"));
+
+        assert_eq!(vars["git.branch"], "main");
+        assert_eq!(vars["git.sha"], SHA);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn detached_head_has_no_branch() {
+        let root = temp_dir("detached");
+        write(root.join("myapp/.git/HEAD"), &format!("{}\n", SHA));
+
+        let vars = variables(&root.join("myapp"));
+
+        assert!(!vars.contains_key("git.branch"));
+        assert!(!vars.contains_key("git.branch_slug"));
+        assert_eq!(vars["git.sha"], SHA);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unborn_branch_has_no_sha() {
+        let root = temp_dir("unborn");
+        write(root.join("myapp/.git/HEAD"), "ref: refs/heads/main\n");
+
+        let vars = variables(&root.join("myapp"));
+
+        assert_eq!(vars["git.branch"], "main");
+        assert!(!vars.contains_key("git.sha"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn linked_worktree_of_a_bare_repository() {
+        let root = temp_dir("bare-worktree");
+        let common_dir = root.join("myapp.git");
+        write(common_dir.join("HEAD"), "ref: refs/heads/main\n");
+        write(
+            common_dir.join("packed-refs"),
+            &format!("{} refs/heads/feature\n", SHA),
+        );
+        let worktree_git_dir = common_dir.join("worktrees/feature");
+        write(worktree_git_dir.join("HEAD"), "ref: refs/heads/feature\n");
+        write(worktree_git_dir.join("commondir"), "../..\n");
+        write(
+            root.join("myapp-feature/.git"),
+            "gitdir: ../myapp.git/worktrees/feature\n",
+        );
+
+        let vars = variables(&root.join("myapp-feature"));
+
+        assert_eq!(vars["git.branch"], "feature");
+        assert_eq!(vars["git.sha"], SHA);
+        assert_eq!(vars["git.worktree_name"], "myapp-feature");
+        assert_eq!(vars["git.repo_name"], "myapp");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn linked_worktree_with_an_absolute_gitdir() {
+        let root = temp_dir("worktree");
+        let common_dir = root.join("myapp/.git");
+        write(common_dir.join("HEAD"), "ref: refs/heads/main\n");
+        write(common_dir.join("refs/heads/fix"), &format!("{}\n", SHA));
+        let worktree_git_dir = common_dir.join("worktrees/myapp-fix");
+        write(worktree_git_dir.join("HEAD"), "ref: refs/heads/fix\n");
+        write(worktree_git_dir.join("commondir"), "../..\n");
+        write(
+            root.join("trees/myapp-fix/.git"),
+            &format!("gitdir: {}\n", worktree_git_dir.display()),
+        );
+
+        let vars = variables(&root.join("trees/myapp-fix"));
+
+        assert_eq!(vars["git.branch"], "fix");
+        assert_eq!(vars["git.sha"], SHA);
+        assert_eq!(vars["git.worktree_name"], "myapp-fix");
+        assert_eq!(vars["git.repo_name"], "myapp");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
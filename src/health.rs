@@ -0,0 +1,417 @@
+// src/health.rs
+//! Reachability checks of reverse_proxy upstreams for `caddy-dev health`.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crate::sites::Site;
+
+/// Where an upstream listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Tcp { host: String, port: u16, tls: bool },
+    Unix(PathBuf),
+}
+
+/// Outcome of probing an upstream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    /// Reachable, with what was checked (e.g. `HTTP 200`)
+    Up(String),
+    /// Unreachable, with the reason
+    Down(String),
+    /// The upstream can't be probed, e.g. it's a placeholder resolved at runtime
+    Skipped(String),
+}
+
+/// Health of one upstream of a site
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamHealth {
+    /// Site address the upstream is proxied from
+    pub site: String,
+    pub upstream: String,
+    pub health: Health,
+}
+
+/// Outcome of [`check`]
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Each upstream of each site, in order
+    pub upstreams: Vec<UpstreamHealth>,
+    /// Distinct upstreams that are down
+    pub down: usize,
+    /// Distinct upstreams probed
+    pub total: usize,
+}
+
+/// Probe the upstreams of `sites`, each one once however many sites share it, all at
+/// the same time
+pub fn check(sites: &[Site], http_path: Option<&str>, timeout: Duration) -> Report {
+    let mut distinct: Vec<&str> = Vec::new();
+    for upstream in sites.iter().flat_map(|site| &site.upstreams) {
+        if !distinct.contains(&upstream.as_str()) {
+            distinct.push(upstream);
+        }
+    }
+
+    let results: HashMap<&str, Health> = thread::scope(|scope| {
+        let probes: Vec<_> = distinct
+            .iter()
+            .map(|upstream| {
+                let probe = scope.spawn(move || match parse_upstream(upstream) {
+                    Ok(target) => probe(&target, http_path, timeout),
+                    Err(reason) => Health::Skipped(reason),
+                });
+                (*upstream, probe)
+            })
+            .collect();
+        probes
+            .into_iter()
+            .map(|(upstream, probe)| {
+                let health = probe
+                    .join()
+                    .unwrap_or_else(|_| Health::Skipped("probe failed".to_string()));
+                (upstream, health)
+            })
+            .collect()
+    });
+
+    let upstreams = sites
+        .iter()
+        .flat_map(|site| {
+            site.upstreams.iter().map(|upstream| UpstreamHealth {
+                site: site.address.clone(),
+                upstream: upstream.clone(),
+                health: results[upstream.as_str()].clone(),
+            })
+        })
+        .collect();
+    Report {
+        upstreams,
+        down: results
+            .values()
+            .filter(|health| matches!(health, Health::Down(_)))
+            .count(),
+        total: results.len(),
+    }
+}
+
+/// Parse a `reverse_proxy` upstream: `localhost:3000`, `:3000`, `http://app:8080`,
+/// `https://api.example.com` or `unix//run/app.sock`
+pub fn parse_upstream(upstream: &str) -> Result<Target, String> {
+    if upstream.contains('{') {
+        return Err("placeholder resolved by Caddy at runtime".to_string());
+    }
+    if let Some(path) = upstream.strip_prefix("unix/") {
+        return Ok(Target::Unix(PathBuf::from(path)));
+    }
+
+    let (scheme, rest) = match upstream.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => ("", upstream),
+    };
+    let tls = scheme == "https";
+    let host_port = rest.split('/').next().unwrap_or(rest);
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (host_port, None),
+    };
+    let port = match port {
+        Some(port) => port
+            .parse::<u16>()
+            .map_err(|_| format!("invalid port '{}'", port))?,
+        None if tls => 443,
+        None => 80,
+    };
+    let host = match host.trim_start_matches('[').trim_end_matches(']') {
+        "" => "localhost",
+        host => host,
+    };
+    Ok(Target::Tcp {
+        host: host.to_string(),
+        port,
+        tls,
+    })
+}
+
+/// Probe an upstream with a TCP connect, then `GET http_path` when given (plain HTTP only)
+pub fn probe(target: &Target, http_path: Option<&str>, timeout: Duration) -> Health {
+    match target {
+        Target::Tcp { host, port, tls } => {
+            let addrs: Vec<SocketAddr> = match (host.as_str(), *port).to_socket_addrs() {
+                Ok(addrs) => addrs.collect(),
+                Err(e) => return Health::Down(format!("cannot resolve '{}': {}", host, e)),
+            };
+            let mut last_error = None;
+            for addr in addrs {
+                match TcpStream::connect_timeout(&addr, timeout) {
+                    Ok(stream) => {
+                        return match http_path {
+                            Some(_) if *tls => Health::Up("TCP connect (HTTPS not probed)".into()),
+                            Some(path) => http_get(stream, host, path, timeout),
+                            None => Health::Up("TCP connect".to_string()),
+                        };
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            match last_error {
+                Some(e) => Health::Down(describe_error(&e)),
+                None => Health::Down(format!("'{}' did not resolve", host)),
+            }
+        }
+        #[cfg(unix)]
+        Target::Unix(path) => match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => Health::Up("unix socket connect".to_string()),
+            Err(e) => Health::Down(describe_error(&e)),
+        },
+        #[cfg(not(unix))]
+        Target::Unix(_) => {
+            Health::Skipped("unix sockets are not supported on this platform".to_string())
+        }
+    }
+}
+
+/// Send a GET request and report the response status
+fn http_get(mut stream: TcpStream, host: &str, path: &str, timeout: Duration) -> Health {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: caddy-dev\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    let mut head = [0u8; 64];
+    let result = stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|()| stream.write_all(request.as_bytes()))
+        .and_then(|()| stream.read(&mut head));
+    match result {
+        Ok(0) => Health::Down("connection closed without a response".to_string()),
+        Ok(read) => {
+            let status_line = String::from_utf8_lossy(&head[..read]);
+            match status_line
+                .strip_prefix("HTTP/")
+                .and_then(|rest| rest.split_whitespace().nth(1))
+            {
+                Some(status) => Health::Up(format!("HTTP {}", status)),
+                None => Health::Down("not an HTTP response".to_string()),
+            }
+        }
+        Err(e) => Health::Down(describe_error(&e)),
+    }
+}
+
+/// Short description of a connection error
+fn describe_error(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => "connection refused".to_string(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => "timed out".to_string(),
+        io::ErrorKind::NotFound => "socket not found".to_string(),
+        _ => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn tcp(host: &str, port: u16, tls: bool) -> Target {
+        Target::Tcp {
+            host: host.to_string(),
+            port,
+            tls,
+        }
+    }
+
+    fn local_target(listener: &TcpListener) -> Target {
+        tcp("127.0.0.1", listener.local_addr().unwrap().port(), false)
+    }
+
+    #[test]
+    fn parse_upstream_cases() {
+        let cases = [
+            (":3000", tcp("localhost", 3000, false)),
+            ("localhost:3000", tcp("localhost", 3000, false)),
+            ("[::1]:8080", tcp("::1", 8080, false)),
+            ("http://app:8080", tcp("app", 8080, false)),
+            ("http://app", tcp("app", 80, false)),
+            ("https://host", tcp("host", 443, true)),
+            ("https://host:8443/path", tcp("host", 8443, true)),
+            (
+                "unix//run/app.sock",
+                Target::Unix(PathBuf::from("/run/app.sock")),
+            ),
+        ];
+        for (upstream, expected) in cases {
+            assert_eq!(parse_upstream(upstream), Ok(expected), "{}", upstream);
+        }
+    }
+
+    #[test]
+    fn parse_upstream_skips_placeholders() {
+        for upstream in [
+            "{upstream}",
+            "localhost:{env.PORT}",
+            "{http.reverse_proxy.upstream}",
+        ] {
+            assert_eq!(
+                parse_upstream(upstream),
+                Err("placeholder resolved by Caddy at runtime".to_string()),
+                "{}",
+                upstream
+            );
+        }
+    }
+
+    #[test]
+    fn parse_upstream_rejects_invalid_ports() {
+        assert_eq!(
+            parse_upstream("localhost:http"),
+            Err("invalid port 'http'".to_string())
+        );
+        assert!(parse_upstream("localhost:70000").is_err());
+    }
+
+    #[test]
+    fn probe_reports_a_listening_port_as_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert_eq!(
+            probe(&local_target(&listener), None, TIMEOUT),
+            Health::Up("TCP connect".to_string())
+        );
+    }
+
+    #[test]
+    fn probe_reports_a_closed_port_as_down() {
+        let target = local_target(&TcpListener::bind("127.0.0.1:0").unwrap());
+        assert_eq!(
+            probe(&target, None, TIMEOUT),
+            Health::Down("connection refused".to_string())
+        );
+    }
+
+    #[test]
+    fn probe_with_http_reports_the_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = local_target(&listener);
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let read = stream.read(&mut request).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+
+        assert_eq!(
+            probe(&target, Some("/healthz"), TIMEOUT),
+            Health::Up("HTTP 503".to_string())
+        );
+        let request = server.join().unwrap();
+        assert!(
+            request.starts_with("GET /healthz HTTP/1.1\r\n"),
+            "{}",
+            request
+        );
+    }
+
+    #[test]
+    fn probe_with_http_reports_non_http_answers_as_down() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = local_target(&listener);
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"SSH-2.0-OpenSSH\r\n").unwrap();
+        });
+
+        assert_eq!(
+            probe(&target, Some("/"), TIMEOUT),
+            Health::Down("not an HTTP response".to_string())
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn probe_does_not_speak_http_to_tls_upstreams() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(
+            probe(&tcp("127.0.0.1", port, true), Some("/"), TIMEOUT),
+            Health::Up("TCP connect (HTTPS not probed)".to_string())
+        );
+    }
+
+    #[test]
+    fn check_probes_shared_upstreams_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let up = format!("127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let down = {
+            let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("127.0.0.1:{}", closed.local_addr().unwrap().port())
+        };
+        let site = |address: &str, upstreams: &[&str]| Site {
+            address: address.to_string(),
+            upstreams: upstreams.iter().map(|u| u.to_string()).collect(),
+            file: PathBuf::from("/dev/app/Caddyfile.dev"),
+            line: 1,
+        };
+        let sites = [
+            site("app.localhost", &[&up, &down]),
+            site("api.localhost", &[&up, "{upstream}"]),
+            site("static.localhost", &[]),
+        ];
+
+        let report = check(&sites, None, TIMEOUT);
+
+        let rows: Vec<(&str, &str, &Health)> = report
+            .upstreams
+            .iter()
+            .map(|u| (u.site.as_str(), u.upstream.as_str(), &u.health))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "app.localhost",
+                    up.as_str(),
+                    &Health::Up("TCP connect".into())
+                ),
+                (
+                    "app.localhost",
+                    down.as_str(),
+                    &Health::Down("connection refused".into())
+                ),
+                (
+                    "api.localhost",
+                    up.as_str(),
+                    &Health::Up("TCP connect".into())
+                ),
+                (
+                    "api.localhost",
+                    "{upstream}",
+                    &Health::Skipped("placeholder resolved by Caddy at runtime".into())
+                ),
+            ]
+        );
+        assert_eq!((report.down, report.total), (1, 3));
+        assert_eq!(check(&[], None, TIMEOUT).total, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probe_reports_a_missing_socket_as_down() {
+        let path =
+            std::env::temp_dir().join(format!("caddy-dev-health-{}.sock", std::process::id()));
+        assert_eq!(
+            probe(&Target::Unix(path), None, TIMEOUT),
+            Health::Down("socket not found".to_string())
+        );
+    }
+}
@@ -0,0 +1,27 @@
+// src/lib.rs
+//! caddy-dev as a library: rendering templates into Caddyfile.dev files, managing the
+//! configuration and the main Caddyfile, and reloading Caddy. The `caddy-dev` binary is
+//! a thin CLI over it.
+//!
+//! Operations return [`CaddyDevError`] on failure and never exit the process.
+
+pub mod admin;
+pub mod config;
+pub mod doctor;
+pub mod error;
+pub mod generate;
+pub mod git;
+pub mod health;
+pub mod logs;
+pub mod ports;
+pub mod process;
+pub mod reload;
+pub mod scan;
+pub mod sites;
+pub mod store;
+pub mod template;
+pub mod validate;
+pub mod vars;
+pub mod watch;
+
+pub use error::CaddyDevError;
@@ -0,0 +1,129 @@
+// src/logs.rs
+//! Human-readable formatting of Caddy's JSON log lines for `caddy-dev run`.
+
+use console::style;
+use serde_json::{Map, Value};
+
+/// Fields shown in the line prefix rather than as `key=value` pairs
+const PREFIX_FIELDS: [&str; 4] = ["level", "ts", "logger", "msg"];
+
+/// Format a Caddy log line as `LEVEL logger message key=value...`.
+/// Lines that aren't JSON objects are returned unchanged.
+pub fn prettify(line: &str) -> String {
+    let Ok(Value::Object(entry)) = serde_json::from_str::<Value>(line) else {
+        return line.to_string();
+    };
+
+    let level = entry.get("level").and_then(Value::as_str).unwrap_or("info");
+    let level_label = format!("{:<5}", level.to_uppercase());
+    let level_label = match level {
+        "error" | "panic" | "fatal" => style(level_label).red().bold(),
+        "warn" => style(level_label).yellow(),
+        "debug" => style(level_label).dim(),
+        _ => style(level_label).green(),
+    };
+
+    let mut pretty = level_label.to_string();
+    if let Some(logger) = entry.get("logger").and_then(Value::as_str) {
+        pretty.push_str(&format!(" {}", style(logger).cyan()));
+    }
+    let message = entry.get("msg").and_then(Value::as_str).unwrap_or_default();
+
+    // Access logs: one line per request instead of the whole request object
+    if message == "handled request"
+        && let Some(request) = entry.get("request").and_then(Value::as_object)
+    {
+        pretty.push_str(&format!(" {}", access_summary(&entry, request)));
+        return pretty;
+    }
+
+    if !message.is_empty() {
+        pretty.push_str(&format!(" {}", message));
+    }
+    for (key, value) in entry
+        .iter()
+        .filter(|(key, _)| !PREFIX_FIELDS.contains(&key.as_str()))
+    {
+        let value = match value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        pretty.push_str(&format!(" {}", style(format!("{}={}", key, value)).dim()));
+    }
+    pretty
+}
+
+/// `GET app.localhost/path → 200 (12ms)` for an access log entry
+fn access_summary(entry: &Map<String, Value>, request: &Map<String, Value>) -> String {
+    let field = |name: &str| {
+        request
+            .get(name)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    };
+    let status = entry.get("status").and_then(Value::as_u64).unwrap_or(0);
+    let status_label = match status {
+        500.. => style(status).red(),
+        400..500 => style(status).yellow(),
+        _ => style(status).green(),
+    };
+
+    let mut summary = format!(
+        "{} {}{} → {}",
+        field("method"),
+        field("host"),
+        field("uri"),
+        status_label
+    );
+    if let Some(duration) = entry.get("duration").and_then(Value::as_f64) {
+        summary.push_str(&format!(" ({:.0}ms)", duration * 1000.0));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(line: &str) -> String {
+        console::set_colors_enabled(false);
+        prettify(line)
+    }
+
+    #[test]
+    fn prettifies_log_lines() {
+        let cases = [
+            (
+                r#"{"level":"info","ts":1700000000.1,"logger":"admin","msg":"admin endpoint started","address":"localhost:2019","enforce_origin":false}"#,
+                "INFO  admin admin endpoint started address=localhost:2019 enforce_origin=false",
+            ),
+            (
+                r#"{"level":"error","ts":1700000000.1,"msg":"dial tcp: connection refused"}"#,
+                "ERROR dial tcp: connection refused",
+            ),
+            (r#"{"msg":"no level"}"#, "INFO  no level"),
+            ("not json at all", "not json at all"),
+            (r#"["an","array"]"#, r#"["an","array"]"#),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(plain(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn summarizes_access_logs() {
+        let line = r#"{"level":"info","ts":1700000000.1,"logger":"http.log.access","msg":"handled request","request":{"method":"GET","host":"app.localhost","uri":"/users?page=2","headers":{"Accept":["*/*"]}},"status":502,"duration":0.0123,"size":0}"#;
+        assert_eq!(
+            plain(line),
+            "INFO  http.log.access GET app.localhost/users?page=2 → 502 (12ms)"
+        );
+
+        let entry = serde_json::json!({"status": 200});
+        let request = serde_json::json!({"method": "POST", "host": "api.localhost", "uri": "/"});
+        console::set_colors_enabled(false);
+        assert_eq!(
+            access_summary(entry.as_object().unwrap(), request.as_object().unwrap()),
+            "POST api.localhost/ → 200"
+        );
+    }
+}
@@ -1,10 +1,21 @@
 // src/main.rs
 use clap::{Parser, Subcommand};
 use dialoguer::{Confirm, Input};
-use dirs::config_dir;
-use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{BufRead, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use caddy_dev::config::{self, Config, normalize_folder};
+use caddy_dev::generate::{self, GenerateOptions};
+use caddy_dev::store::{
+    get_config_dir, get_config_path, get_main_caddyfile_path, load_config, prepare_main_caddyfile,
+    save_config,
+};
+use caddy_dev::vars::{parse_key_val, parse_var_name};
+use caddy_dev::{
+    CaddyDevError, admin, doctor, health, logs, ports, process, reload, scan, sites, store,
+    validate, watch,
+};
 
 /// Simple generator for Caddyfile.dev from a template with {{key}} placeholders
 #[derive(Parser, Debug)]
@@ -18,288 +29,1383 @@ struct Args {
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Generate Caddyfile.dev from a template
-    Generate {
-        /// Output directory where Caddyfile.dev will be created (default: current directory)
-        #[arg(short = 'o', long = "output-dir", value_name = "DIR")]
-        output_dir: Option<PathBuf>,
-
-        /// Full path to the template file (default: <output-dir>/Caddyfile.template)
-        #[arg(short = 't', long = "template", value_name = "FILE")]
-        template: Option<PathBuf>,
-
-        /// Variables in key=value format (can be repeated)
-        #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_key_val)]
-        variables: Vec<(String, String)>,
-    },
+    Generate(GenerateArgs),
 
     /// Initialize caddy-dev by setting up folders to import Caddyfile.dev from
-    Init,
+    Init(InitArgs),
 
     /// Reload Caddy with the generated config
-    Reload,
+    Reload(ReloadArgs),
+
+    /// Check the main Caddyfile and every imported Caddyfile.dev for errors
+    Validate,
+
+    /// List the sites served by the imported Caddyfile.dev files
+    Sites(SitesArgs),
+
+    /// Look for sites defined in more than one imported Caddyfile.dev
+    Check,
+
+    /// Check that the reverse_proxy upstreams of every site are reachable
+    Health(HealthArgs),
+
+    /// Diagnose the local setup: Caddy, admin endpoint, config, folders and local CA
+    Doctor,
+
+    /// Find projects with a Caddyfile.template or Caddyfile.dev under a directory tree
+    Scan(ScanArgs),
+
+    /// Regenerate Caddyfile.dev files and reload Caddy whenever templates change
+    Watch(WatchArgs),
+
+    /// Run Caddy in the foreground, reloading it whenever projects change
+    Run(RunArgs),
+
+    /// Start Caddy in the background with the main Caddyfile
+    Start,
+
+    /// Stop Caddy
+    Stop,
+
+    /// Show whether Caddy is running, its admin endpoint and the config it loaded
+    Status,
+
+    /// Manage the folders Caddyfile.dev files are imported from
+    Folders {
+        #[command(subcommand)]
+        command: FoldersCommand,
+    },
+
+    /// Show the caddy-dev configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Manage ports allocated to projects for {{port:name}} placeholders
+    Port {
+        #[command(subcommand)]
+        command: PortCommand,
+    },
+
+    /// Manage global variables shared by every template
+    Vars {
+        #[command(subcommand)]
+        command: VarsCommand,
+    },
 }
 
-/// Parse a single key=value pair
-fn parse_key_val(s: &str) -> Result<(String, String), String> {
-    let parts: Vec<&str> = s.splitn(2, '=').collect();
-    if parts.len() != 2 {
-        return Err(format!(
-            "Invalid variable format: '{}'. Expected key=value",
-            s
-        ));
-    }
-    Ok((parts[0].to_string(), parts[1].to_string()))
+/// Subcommands of `caddy-dev folders`
+#[derive(Subcommand, Debug)]
+enum FoldersCommand {
+    /// Add folders (or glob patterns) to import Caddyfile.dev from
+    Add {
+        /// Folders or glob patterns
+        #[arg(required = true, value_name = "PATH")]
+        folders: Vec<String>,
+    },
+
+    /// Stop importing Caddyfile.dev from folders
+    Remove {
+        /// Folders or glob patterns, as shown by 'caddy-dev folders list'
+        #[arg(required = true, value_name = "PATH")]
+        folders: Vec<String>,
+    },
+
+    /// List configured folders and their import patterns
+    List,
 }
 
-/// Get the caddy-dev config directory (~/.config/caddy-dev)
-fn get_config_dir() -> PathBuf {
-    // Use XDG-compliant ~/.config/caddy-dev for cross-platform consistency
-    // This overrides the platform-specific config_dir() to ensure consistent behavior
-    if let Some(home_dir) = dirs::home_dir() {
-        home_dir.join(".config").join("caddy-dev")
-    } else {
-        // Fallback to platform-specific config directory if home not found
-        config_dir()
-            .unwrap_or_else(|| PathBuf::from("/home/.config"))
-            .join("caddy-dev")
-    }
+/// Subcommands of `caddy-dev config`
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the path of config.toml
+    Path,
+
+    /// Print the effective configuration, including defaults
+    Show,
 }
 
-/// Get the main Caddyfile path in config directory
-fn get_main_caddyfile_path() -> PathBuf {
-    get_config_dir().join("Caddyfile")
+/// Subcommands of `caddy-dev port`
+#[derive(Subcommand, Debug)]
+enum PortCommand {
+    /// Print the port allocated to a name, allocating a free one if needed
+    Allocate {
+        /// Name of the service (e.g. web, api)
+        name: String,
+
+        /// Project directory the port belongs to (default: current directory)
+        #[arg(short = 'p', long = "project", value_name = "DIR")]
+        project: Option<PathBuf>,
+    },
+
+    /// List allocated ports
+    List,
+
+    /// Release an allocated port
+    Release {
+        /// Name of the service (e.g. web, api)
+        name: String,
+
+        /// Project directory the port belongs to (default: current directory)
+        #[arg(short = 'p', long = "project", value_name = "DIR")]
+        project: Option<PathBuf>,
+    },
 }
 
-/// Generate Caddyfile.dev from template
-fn generate_caddyfile_dev(
+/// Subcommands of `caddy-dev vars`
+#[derive(Subcommand, Debug)]
+enum VarsCommand {
+    /// Set a global variable
+    Set {
+        /// Name of the variable
+        #[arg(value_parser = parse_var_name)]
+        name: String,
+
+        /// Value of the variable
+        value: String,
+    },
+
+    /// Print the value of a global variable
+    Get {
+        /// Name of the variable
+        name: String,
+    },
+
+    /// List global variables as key=value lines
+    List,
+
+    /// Remove a global variable
+    Unset {
+        /// Name of the variable
+        name: String,
+    },
+}
+
+/// Options for the generate command
+#[derive(clap::Args, Debug)]
+struct GenerateArgs {
+    /// Output directory where Caddyfile.dev will be created (default: current directory)
+    #[arg(short = 'o', long = "output-dir", value_name = "DIR")]
     output_dir: Option<PathBuf>,
+
+    /// Full path to the template file (default: <output-dir>/Caddyfile.template, see config.toml)
+    #[arg(short = 't', long = "template", value_name = "FILE")]
     template: Option<PathBuf>,
+
+    /// Variables in key=value format (can be repeated, overrides the vars file)
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_key_val)]
     variables: Vec<(String, String)>,
-) {
-    // Output directory (default: current)
-    let output_dir = output_dir.unwrap_or_else(|| PathBuf::from("."));
-    if !output_dir.is_dir() {
-        eprintln!(
-            "Error: Output directory '{}' does not exist or is not a directory.",
-            output_dir.display()
-        );
-        std::process::exit(1);
-    }
 
-    // Template path (default: output_dir/Caddyfile.template)
-    let template_path = template.unwrap_or_else(|| output_dir.join("Caddyfile.template"));
+    /// Variables file with key=value lines (default: <output-dir>/Caddyfile.vars if present, see config.toml)
+    #[arg(long = "vars-file", value_name = "FILE")]
+    vars_file: Option<PathBuf>,
 
-    // Read template content
-    let template_content = match fs::read_to_string(&template_path) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!(
-                "Error reading template '{}': {}",
-                template_path.display(),
-                e
-            );
-            std::process::exit(1);
+    /// Load <output-dir>/.env and <output-dir>/.env.local for {{env.NAME}} lookups
+    #[arg(long = "dotenv")]
+    dotenv: bool,
+
+    /// Dotenv file to load for {{env.NAME}} lookups (can be repeated)
+    #[arg(long = "env-file", value_name = "FILE")]
+    env_files: Vec<PathBuf>,
+
+    /// Write Caddyfile.dev even if some placeholders have no value (they are left as-is)
+    #[arg(long = "allow-missing")]
+    allow_missing: bool,
+
+    /// Generate every project with a template under the configured folders, then reload Caddy
+    #[arg(long = "all", conflicts_with_all = ["output_dir", "template", "vars_file"])]
+    all: bool,
+
+    /// With --all, don't reload Caddy after generating
+    #[arg(long = "no-reload", requires = "all")]
+    no_reload: bool,
+}
+
+/// Options for the init command
+#[derive(clap::Args, Debug)]
+struct InitArgs {
+    /// Folder or glob pattern to import Caddyfile.dev from (can be repeated, '-' reads them from stdin)
+    #[arg(short = 'f', long = "folder", value_name = "PATH")]
+    folders: Vec<String>,
+
+    /// Overwrite an existing configuration without asking
+    #[arg(long = "force")]
+    force: bool,
+
+    /// Never prompt; fail instead if input would be required
+    #[arg(long = "no-input")]
+    no_input: bool,
+}
+
+/// Options for the reload command
+#[derive(clap::Args, Debug)]
+struct ReloadArgs {
+    /// Reload with 'caddy reload' instead of the admin API
+    #[arg(long = "cli")]
+    cli: bool,
+
+    /// Skip 'caddy validate' before reloading
+    #[arg(long = "no-validate")]
+    no_validate: bool,
+
+    /// Validate each imported Caddyfile.dev on its own and leave out the broken ones
+    #[arg(long = "skip-broken", conflicts_with = "no_validate")]
+    skip_broken: bool,
+}
+
+/// Options for the sites command
+#[derive(clap::Args, Debug)]
+struct SitesArgs {
+    /// Print the sites as JSON
+    #[arg(long = "json")]
+    json: bool,
+}
+
+/// Options for the health command
+#[derive(clap::Args, Debug)]
+struct HealthArgs {
+    /// Also send an HTTP GET request to each upstream (default path: /)
+    #[arg(long = "http", value_name = "PATH", num_args = 0..=1, default_missing_value = "/")]
+    http: Option<String>,
+
+    /// Milliseconds to wait for each upstream to answer
+    #[arg(long = "timeout", value_name = "MS", default_value_t = 1000)]
+    timeout: u64,
+}
+
+/// Options for the scan command
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
+    /// Directory to search, e.g. where repositories are cloned
+    #[arg(value_name = "ROOT")]
+    root: PathBuf,
+
+    /// How many directory levels below ROOT to look for projects
+    #[arg(long = "max-depth", value_name = "N", default_value_t = 3)]
+    max_depth: usize,
+
+    /// Add the parent folders of projects that aren't imported yet to the configuration
+    #[arg(long = "add")]
+    add: bool,
+}
+
+/// Options for the watch command
+#[derive(clap::Args, Debug)]
+struct WatchArgs {
+    /// Milliseconds to wait for further changes before acting on a burst of events
+    #[arg(long = "debounce", value_name = "MS", default_value_t = 300)]
+    debounce: u64,
+
+    /// Only regenerate Caddyfile.dev files, don't reload Caddy
+    #[arg(long = "no-reload")]
+    no_reload: bool,
+}
+
+/// Options for the run command
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// Milliseconds to wait for further changes before acting on a burst of events
+    #[arg(long = "debounce", value_name = "MS", default_value_t = 300)]
+    debounce: u64,
+
+    /// Print Caddy's logs as JSON, as Caddy writes them
+    #[arg(long = "raw-logs")]
+    raw_logs: bool,
+}
+
+impl From<GenerateArgs> for GenerateOptions {
+    fn from(args: GenerateArgs) -> Self {
+        GenerateOptions {
+            output_dir: args.output_dir,
+            template: args.template,
+            variables: args.variables,
+            vars_file: args.vars_file,
+            dotenv: args.dotenv,
+            env_files: args.env_files,
+            allow_missing: args.allow_missing,
         }
-    };
+    }
+}
+
+/// Exit code of an error, distinct for each kind so scripts can tell them apart.
+/// 2 is left to command-line usage errors.
+fn exit_code(error: &CaddyDevError) -> i32 {
+    match error {
+        CaddyDevError::Io { .. } => 1,
+        CaddyDevError::ConfigMissing { .. } => 3,
+        CaddyDevError::ConfigInvalid { .. } => 4,
+        CaddyDevError::OutputDirNotFound { .. } => 5,
+        CaddyDevError::TemplateNotFound { .. } => 6,
+        CaddyDevError::TemplateSyntax { .. } => 7,
+        CaddyDevError::UnresolvedPlaceholder { .. } => 8,
+        CaddyDevError::VarsFile { .. } => 9,
+        CaddyDevError::PortRegistry { .. } => 10,
+        CaddyDevError::PortUnavailable { .. } => 11,
+        CaddyDevError::CaddyNotFound { .. } => 12,
+        CaddyDevError::InvalidCaddyfile { .. } => 13,
+        CaddyDevError::DuplicateSites(_) => 14,
+        CaddyDevError::CaddyNotRunning { .. } => 15,
+        CaddyDevError::CaddyAlreadyRunning { .. } => 16,
+        CaddyDevError::ReloadFailed { .. } => 17,
+        CaddyDevError::StartFailed { .. } => 18,
+        CaddyDevError::StopFailed { .. } => 19,
+        CaddyDevError::Watch(_) => 20,
+        CaddyDevError::OverlappingFolders(_) => 21,
+        CaddyDevError::NonInteractive { .. } => 22,
+        CaddyDevError::ConfigExists { .. } => 23,
+        CaddyDevError::FolderNotConfigured { .. } => 24,
+        CaddyDevError::PortNotAllocated { .. } => 25,
+        CaddyDevError::VarNotSet { .. } => 26,
+        CaddyDevError::UpstreamsDown { .. } => 27,
+        CaddyDevError::ChecksFailed { .. } => 28,
+        CaddyDevError::ProjectsFailed { .. } => 29,
+        CaddyDevError::CaddyExited { .. } => 30,
+        CaddyDevError::Stdio { .. } => 31,
+    }
+}
 
-    // Collect variables into a HashMap
-    let vars: HashMap<String, String> = variables.into_iter().collect();
+/// What to do about an error, when there's a command for it
+fn hint(error: &CaddyDevError) -> Option<String> {
+    match error {
+        CaddyDevError::ConfigMissing { .. } => {
+            Some("Run 'caddy-dev init' first to set up the configuration.".to_string())
+        }
+        CaddyDevError::ConfigInvalid { path, .. } => {
+            Some(format!("Fix the syntax of {}", path.display()))
+        }
+        CaddyDevError::UnresolvedPlaceholder { .. } => Some(
+            "Provide the missing values with --var KEY=VALUE, or pass --allow-missing to keep them as-is."
+                .to_string(),
+        ),
+        CaddyDevError::CaddyNotFound { .. } => Some(format!(
+            "Install Caddy or set 'caddy_bin' in {}",
+            get_config_path().display()
+        )),
+        CaddyDevError::DuplicateSites(_) => Some(
+            "Caddy refuses to load a configuration defining the same site more than once."
+                .to_string(),
+        ),
+        CaddyDevError::OverlappingFolders(_) => Some(
+            "Remove one of the overlapping folders with 'caddy-dev folders remove'.".to_string(),
+        ),
+        CaddyDevError::CaddyNotRunning { .. } => Some("Start it with: caddy-dev start".to_string()),
+        CaddyDevError::CaddyAlreadyRunning { .. } => Some(
+            "Apply the configuration with 'caddy-dev reload', or stop it with 'caddy-dev stop'."
+                .to_string(),
+        ),
+        CaddyDevError::NonInteractive { .. } => Some(
+            "Pass them with 'caddy-dev init --folder <PATH>' (repeatable) or '--folder -' to read them from stdin."
+                .to_string(),
+        ),
+        CaddyDevError::ConfigExists { .. } => Some("Pass --force to overwrite it.".to_string()),
+        CaddyDevError::FolderNotConfigured { .. } => {
+            Some("Run 'caddy-dev folders list' to see configured folders.".to_string())
+        }
+        CaddyDevError::PortNotAllocated { .. } => {
+            Some("List allocations with: caddy-dev port list".to_string())
+        }
+        CaddyDevError::VarNotSet { .. } => {
+            Some("List global variables with: caddy-dev vars list".to_string())
+        }
+        _ => None,
+    }
+}
 
-    // Perform substitutions
-    let mut result = template_content;
-    for (key, value) in &vars {
-        let placeholder = format!("{{{{{}}}}}", key);
-        result = result.replace(&placeholder, value);
+/// Report an error with its warnings and hint, and exit with its exit code
+fn fail(error: &CaddyDevError) -> ! {
+    print_warnings(error.warnings());
+    eprintln!("Error: {}", error);
+    if let Some(hint) = hint(error) {
+        eprintln!("{}", hint);
     }
+    std::process::exit(exit_code(error));
+}
 
-    // Final output path
-    let output_path = output_dir.join("Caddyfile.dev");
+/// Print warnings returned by the library
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
 
-    // Write the result
-    if let Err(e) = fs::write(&output_path, result) {
-        eprintln!("Error writing '{}': {}", output_path.display(), e);
-        std::process::exit(1);
+/// Generate Caddyfile.dev from template
+fn generate_caddyfile_dev(args: GenerateArgs) -> Result<(), CaddyDevError> {
+    if args.all {
+        return generate_all_projects(args);
     }
+    let config = load_config()?;
+    let generated = generate::generate(args.into(), &config)?;
 
+    print_warnings(&generated.warnings);
+    for (name, port) in &generated.allocated_ports {
+        println!("Allocated port {} for '{}'", port, name);
+    }
     println!(
         "Caddyfile.dev successfully generated at: {}",
-        output_path.display()
+        generated.output_path.display()
     );
-    if !vars.is_empty() {
-        println!("Applied variables: {:?}", vars.keys().collect::<Vec<_>>());
+    if let Some(path) = &generated.vars_file {
+        println!("Loaded variables from: {}", path.display());
+    }
+    for path in &generated.env_paths {
+        println!("Loaded env file: {}", path.display());
+    }
+    if !generated.applied.is_empty() {
+        println!("Applied variables: {:?}", generated.applied);
     } else {
         println!("No variables provided → template copied without changes.");
     }
     println!("Reload Caddy with: caddy-dev reload");
+    Ok(())
 }
 
-/// Interactive initialization to set up import folders
-fn init_caddydev() {
-    println!("=== Caddy-dev Initialization ===");
-    println!("This will help you configure which folders to import Caddyfile.dev from.");
-    println!();
-
-    // Get or create config directory
-    let config_dir = get_config_dir();
-    if let Err(e) = fs::create_dir_all(&config_dir) {
-        eprintln!(
-            "Error creating config directory '{}': {}",
-            config_dir.display(),
-            e
+/// Generate every project under the configured folders, then reload Caddy once
+fn generate_all_projects(args: GenerateArgs) -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let reload = !args.no_reload;
+    let outcomes = generate::generate_all(&args.into(), &config);
+    if outcomes.is_empty() {
+        println!(
+            "No {} found under the configured folders.",
+            config.generate.template
         );
-        std::process::exit(1);
+        return Ok(());
     }
 
-    // Check if there's an existing configuration
-    let main_caddyfile_path = get_main_caddyfile_path();
-    let has_existing = main_caddyfile_path.exists();
+    let mut failed = 0;
+    for outcome in &outcomes {
+        match &outcome.result {
+            Ok(generated) => {
+                println!("✔ {}", generated.output_path.display());
+                for (name, port) in &generated.allocated_ports {
+                    println!("    Allocated port {} for '{}'", port, name);
+                }
+                for warning in &generated.warnings {
+                    println!("    Warning: {}", warning);
+                }
+            }
+            Err(error) => {
+                failed += 1;
+                println!("✘ {}", outcome.dir.display());
+                for warning in error.warnings() {
+                    println!("    Warning: {}", warning);
+                }
+                for line in error.to_string().lines() {
+                    println!("    {}", line);
+                }
+            }
+        }
+    }
+    println!(
+        "\n{} project(s) generated, {} failed.",
+        outcomes.len() - failed,
+        failed
+    );
 
-    if has_existing {
-        println!(
-            "Found existing configuration at: {}",
-            main_caddyfile_path.display()
-        );
-        if !Confirm::new()
-            .with_prompt("Do you want to overwrite it?")
-            .default(false)
-            .interact()
-            .expect("Failed to read input")
-        {
-            println!("Keeping existing configuration.");
-            return;
+    if reload && failed < outcomes.len() {
+        let main_caddyfile_path = prepare_main_caddyfile(&config)?;
+        match reload::apply(&config, &main_caddyfile_path) {
+            Ok(warnings) => {
+                print_warnings(&warnings);
+                println!("Caddy successfully reloaded!");
+            }
+            Err(CaddyDevError::CaddyNotRunning { .. }) => {
+                println!("Caddy is not running. Start it with: caddy-dev start");
+            }
+            Err(e) => return Err(e),
         }
     }
 
-    // Interactive folder selection
-    println!("Enter the folders (or glob patterns) containing Caddyfile.dev files.");
-    println!("Examples:");
-    println!("  - /path/to/project");
-    println!("  - /path/to/**/Caddyfile.dev");
-    println!("  - ~/projects/*/Caddyfile.dev");
-    println!();
-    println!("Press Enter after each entry. Enter an empty line when done.");
+    if failed > 0 {
+        return Err(CaddyDevError::ProjectsFailed {
+            failed,
+            total: outcomes.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Manage the port registry
+fn manage_ports(command: PortCommand) -> Result<(), CaddyDevError> {
+    let mut registry = store::load_port_registry()?;
+    let current_project = |project: Option<PathBuf>| {
+        ports::project_key(&project.unwrap_or_else(|| PathBuf::from(".")))
+    };
+
+    match command {
+        PortCommand::Allocate { name, project } => {
+            let project = current_project(project);
+            let (port, allocated) = registry.allocate(&project, &name)?;
+            if allocated {
+                registry.save()?;
+            }
+            println!("{}", port);
+        }
+        PortCommand::List => {
+            if registry.entries().is_empty() {
+                println!("No ports allocated.");
+                return Ok(());
+            }
+            let mut entries = registry.entries().to_vec();
+            entries.sort_by(|a, b| (&a.project, &a.name).cmp(&(&b.project, &b.name)));
+            println!("{:<6} {:<10} {:<12} PROJECT", "PORT", "STATUS", "NAME");
+            for entry in entries {
+                let status = if ports::is_port_free(entry.port) {
+                    "free"
+                } else {
+                    "listening"
+                };
+                println!(
+                    "{:<6} {:<10} {:<12} {}",
+                    entry.port, status, entry.name, entry.project
+                );
+            }
+        }
+        PortCommand::Release { name, project } => {
+            let project = current_project(project);
+            match registry.release(&project, &name) {
+                Some(port) => {
+                    registry.save()?;
+                    println!("Released port {} ('{}' in {})", port, name, project);
+                }
+                None => return Err(CaddyDevError::PortNotAllocated { name, project }),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Manage the global variables in config.toml
+fn manage_vars(command: VarsCommand) -> Result<(), CaddyDevError> {
+    let mut config = load_config()?;
 
+    match command {
+        VarsCommand::Set { name, value } => {
+            config.vars.insert(name.clone(), value.clone());
+            save_config(&config)?;
+            println!("Set {}={}", name, value);
+        }
+        VarsCommand::Get { name } => match config.vars.get(&name) {
+            Some(value) => println!("{}", value),
+            None => return Err(CaddyDevError::VarNotSet { name }),
+        },
+        VarsCommand::List => {
+            if config.vars.is_empty() {
+                println!("No global variables set.");
+            }
+            for (name, value) in &config.vars {
+                println!("{}={}", name, value);
+            }
+        }
+        VarsCommand::Unset { name } => {
+            if config.vars.remove(&name).is_none() {
+                return Err(CaddyDevError::VarNotSet { name });
+            }
+            save_config(&config)?;
+            println!("Unset {}", name);
+        }
+    }
+    Ok(())
+}
+
+/// Error reading from stdin
+fn stdin_error(source: std::io::Error) -> CaddyDevError {
+    CaddyDevError::Stdio {
+        stream: "stdin",
+        source,
+    }
+}
+
+/// Error of a failed prompt (e.g. stdin closed)
+fn prompt_error(error: dialoguer::Error) -> CaddyDevError {
+    match error {
+        dialoguer::Error::IO(source) => stdin_error(source),
+    }
+}
+
+/// Read folders from stdin, one per line
+fn read_folders_from_stdin() -> Result<Vec<String>, CaddyDevError> {
+    let mut folders = Vec::new();
+    for line in std::io::stdin().lines() {
+        let line = line.map_err(stdin_error)?;
+        if !line.trim().is_empty() {
+            folders.push(line.trim().to_string());
+        }
+    }
+    Ok(folders)
+}
+
+/// Initialization to set up import folders, interactive unless folders are given as flags
+fn init_caddydev(args: InitArgs) -> Result<(), CaddyDevError> {
+    let InitArgs {
+        folders: folder_args,
+        force,
+        no_input,
+    } = args;
+    let interactive = !no_input && std::io::stdin().is_terminal();
+
+    // Folders from flags ('-' reads them from stdin)
     let mut folders: Vec<String> = Vec::new();
+    for folder in folder_args {
+        if folder == "-" {
+            folders.extend(read_folders_from_stdin()?);
+        } else {
+            folders.push(folder);
+        }
+    }
+    let prompt_for_folders = folders.is_empty();
 
-    loop {
-        let input: String = Input::new()
-            .with_prompt(format!("Folder {} (or glob pattern)", folders.len() + 1))
-            .allow_empty(true)
-            .interact()
-            .expect("Failed to read input");
+    if prompt_for_folders {
+        if !interactive {
+            return Err(CaddyDevError::NonInteractive {
+                message: "No folders given and input is not interactive".to_string(),
+            });
+        }
+        println!("=== Caddy-dev Initialization ===");
+        println!("This will help you configure which folders to import Caddyfile.dev from.");
+        println!();
+    }
+
+    // Get or create config directory
+    store::create_config_dir()?;
 
-        if input.trim().is_empty() {
-            break;
+    if let Some(existing) = store::existing_config().filter(|_| !force) {
+        if !interactive {
+            return Err(CaddyDevError::ConfigExists { path: existing });
         }
+        println!("Found existing configuration at: {}", existing.display());
+        let overwrite = Confirm::new()
+            .with_prompt("Do you want to overwrite it?")
+            .default(false)
+            .interact()
+            .map_err(prompt_error)?;
+        if !overwrite {
+            println!("Keeping existing configuration.");
+            return Ok(());
+        }
+    }
+
+    if prompt_for_folders {
+        // Interactive folder selection
+        println!("Enter the folders (or glob patterns) containing Caddyfile.dev files.");
+        println!("Examples:");
+        println!("  - /path/to/project");
+        println!("  - /path/to/**/Caddyfile.dev");
+        println!("  - ~/projects/*/Caddyfile.dev");
+        println!();
+        println!("Press Enter after each entry. Enter an empty line when done.");
+
+        loop {
+            let input: String = Input::new()
+                .with_prompt(format!("Folder {} (or glob pattern)", folders.len() + 1))
+                .allow_empty(true)
+                .interact()
+                .map_err(prompt_error)?;
 
-        // Expand home directory if present
-        let expanded = if input.starts_with("~") {
-            match dirs::home_dir() {
-                Some(home) => home.join(&input[2..]).to_string_lossy().into_owned(),
-                None => input,
+            if input.trim().is_empty() {
+                break;
             }
-        } else {
-            input
-        };
 
-        folders.push(expanded);
+            folders.push(input.trim().to_string());
+        }
     }
 
     if folders.is_empty() {
         println!("No folders specified. Configuration not saved.");
-        return;
+        return Ok(());
     }
 
-    // Generate the main Caddyfile with imports
-    let mut caddyfile_content = String::new();
-    caddyfile_content.push_str("# Auto-generated by caddy-dev init\n");
-    caddyfile_content.push_str("# Edit this file or run 'caddy-dev init' to reconfigure\n\n");
+    // Save the configuration and generate the main Caddyfile with imports
+    let config = store::init_folders(&folders)?;
 
-    // Process each folder/pattern and add imports
-    caddyfile_content.push_str("# Import Caddyfile.dev files from configured folders\n");
+    println!();
+    println!("Configuration saved to: {}", get_config_path().display());
+    println!(
+        "Main Caddyfile written to: {}",
+        get_main_caddyfile_path().display()
+    );
+    println!("Imported {} folder(s).", config.folders.len());
+    println!("Run 'caddy-dev reload' to apply the configuration.");
+    Ok(())
+}
 
-    for folder in &folders {
-        caddyfile_content.push_str(&format!("# Pattern: {}\n", folder));
-        let clean_folder = folder.trim_end_matches('/');
+/// Add, remove or list import folders
+fn manage_folders(command: FoldersCommand) -> Result<(), CaddyDevError> {
+    match command {
+        FoldersCommand::Add { folders } => {
+            for (folder, added) in store::add_folders(&folders)? {
+                if added {
+                    println!("Added: {}", folder);
+                } else {
+                    println!("Already configured: {}", folder);
+                }
+            }
+            println!("Run 'caddy-dev reload' to apply the configuration.");
+        }
+        FoldersCommand::Remove { folders } => {
+            let result = store::remove_folders(&folders)?;
+            for folder in &result.removed {
+                println!("Removed: {}", folder);
+            }
+            if !result.removed.is_empty() {
+                println!("Run 'caddy-dev reload' to apply the configuration.");
+            }
+            if !result.missing.is_empty() {
+                return Err(CaddyDevError::FolderNotConfigured {
+                    folders: result.missing,
+                });
+            }
+        }
+        FoldersCommand::List => {
+            let config = load_config()?;
+            if config.folders.is_empty() {
+                println!("No folders configured. Add one with 'caddy-dev folders add <PATH>'.");
+                return Ok(());
+            }
+            let files = config.imported_files();
+            for folder in &config.folders {
+                let pattern = config::import_pattern(folder);
+                println!("{}", folder);
+                if config::is_recursive(&pattern) {
+                    let matched = files.iter().filter(|file| &file.folder == folder).count();
+                    println!("  import {} (expanded to {} file(s))", pattern, matched);
+                } else {
+                    println!("  import {}", pattern);
+                }
+            }
+        }
+    }
+    Ok(())
+}
 
-        // Check if it's a glob pattern (contains * or ?)
-        if clean_folder.contains('*') || clean_folder.contains('?') {
-            // It's a glob pattern - use it directly
-            caddyfile_content.push_str(&format!("import {}\n", clean_folder));
-        } else {
-            // It's a directory path - clean trailing slashes and generate glob pattern
-            // Caddy glob patterns only support single wildcards, not **
-            caddyfile_content.push_str(&format!("import {}/*/Caddyfile.dev\n", clean_folder));
+/// Show the configuration
+fn show_config(command: ConfigCommand) -> Result<(), CaddyDevError> {
+    match command {
+        ConfigCommand::Path => println!("{}", get_config_path().display()),
+        ConfigCommand::Show => {
+            let content =
+                load_config()?
+                    .to_toml()
+                    .map_err(|message| CaddyDevError::ConfigInvalid {
+                        path: get_config_path(),
+                        message,
+                    })?;
+            print!("{}", content);
         }
     }
+    Ok(())
+}
+
+/// Validate the main Caddyfile with 'caddy validate', falling back to the admin API
+fn validate_caddy() -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let main_caddyfile_path = prepare_main_caddyfile(&config)?;
 
-    // Write the main Caddyfile
-    if let Err(e) = fs::write(&main_caddyfile_path, caddyfile_content) {
+    match validate::run_caddy_validate(&config.caddy_bin, &main_caddyfile_path) {
+        validate::Validation::Valid => {
+            println!("Configuration is valid: {}", main_caddyfile_path.display());
+        }
+        validate::Validation::Invalid(message) => {
+            return Err(CaddyDevError::InvalidCaddyfile {
+                report: reload::caddy_error_report(&config, &main_caddyfile_path, &message),
+            });
+        }
+        validate::Validation::Unavailable(e) => {
+            // Without the binary, a running Caddy can still check the syntax by adapting it
+            eprintln!(
+                "Warning: Cannot execute '{}' ({}); checking syntax through the admin API only",
+                config.caddy_bin, e
+            );
+            let caddyfile = fs::read_to_string(&main_caddyfile_path).unwrap_or_default();
+            match admin::AdminClient::new(&config.admin).adapt_caddyfile(&caddyfile) {
+                Ok(adapted) => {
+                    print_warnings(&adapted.warnings);
+                    println!(
+                        "Caddyfile syntax is valid: {}",
+                        main_caddyfile_path.display()
+                    );
+                }
+                Err(admin::AdminError::Api { body, .. }) => {
+                    return Err(CaddyDevError::InvalidCaddyfile {
+                        report: reload::caddy_error_report(&config, &main_caddyfile_path, &body),
+                    });
+                }
+                Err(_) => {
+                    return Err(CaddyDevError::CaddyNotFound {
+                        bin: config.caddy_bin,
+                        source: e,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print which files were left out of the reload and why
+fn report_skipped_files(broken: &[validate::BrokenFile]) {
+    if broken.is_empty() {
+        return;
+    }
+    eprintln!();
+    eprintln!("Skipped {} broken file(s):", broken.len());
+    for b in broken {
         eprintln!(
-            "Error writing configuration to '{}': {}",
-            main_caddyfile_path.display(),
-            e
+            "  {} (from folder '{}')",
+            b.file.path.display(),
+            b.file.folder
+        );
+        for line in b.message.trim().lines() {
+            eprintln!("    {}", line);
+        }
+    }
+}
+
+/// Reload Caddy with the generated config
+fn reload_caddy(args: ReloadArgs) -> Result<(), CaddyDevError> {
+    let mut config = load_config()?;
+    if args.cli {
+        config.reload = config::ReloadMethod::Cli;
+    }
+    let main_caddyfile_path = prepare_main_caddyfile(&config)?;
+
+    let mut broken = Vec::new();
+    let result = if args.skip_broken {
+        println!(
+            "Checking {} imported file(s)...",
+            config.imported_files().len()
+        );
+        // Broken files were left out by importing the others one by one
+        broken = reload::exclude_broken_files(&config, &main_caddyfile_path)?;
+        if !args.no_validate {
+            let imported: Vec<config::ImportedFile> = config
+                .imported_files()
+                .into_iter()
+                .filter(|file| !broken.iter().any(|b| b.file.path == file.path))
+                .collect();
+            reload::check_duplicate_sites(&imported)?;
+            reload::check_before_reload(&config, &main_caddyfile_path)?;
+        }
+        reload::load_if_running(&config, &main_caddyfile_path)
+    } else if args.no_validate {
+        reload::load_if_running(&config, &main_caddyfile_path)
+    } else {
+        reload::apply(&config, &main_caddyfile_path)
+    };
+
+    match result {
+        Ok(warnings) => {
+            print_warnings(&warnings);
+            println!(
+                "Caddy successfully reloaded with config: {}",
+                main_caddyfile_path.display()
+            );
+        }
+        Err(CaddyDevError::CaddyNotRunning { .. }) => {
+            offer_to_start(&config, &main_caddyfile_path)?;
+        }
+        Err(e) => return Err(e),
+    }
+    report_skipped_files(&broken);
+    Ok(())
+}
+
+/// Offer to start Caddy when a reload finds it isn't running, failing if it isn't started
+fn offer_to_start(config: &Config, main_caddyfile_path: &Path) -> Result<(), CaddyDevError> {
+    let not_running = CaddyDevError::CaddyNotRunning {
+        address: config.admin.clone(),
+    };
+    if !std::io::stdin().is_terminal() {
+        return Err(not_running);
+    }
+
+    let start = Confirm::new()
+        .with_prompt(format!("{}. Start it now?", not_running))
+        .default(true)
+        .interact()
+        .map_err(prompt_error)?;
+    if !start {
+        return Err(not_running);
+    }
+    start_in_background(config, main_caddyfile_path)
+}
+
+/// List the site addresses of every imported Caddyfile.dev with their upstreams
+fn list_sites(args: SitesArgs) -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let (sites, unreadable) = sites::collect_sites(&config.imported_files());
+    for (path, e) in &unreadable {
+        eprintln!("Warning: Cannot read '{}': {}", path.display(), e);
+    }
+
+    if args.json {
+        let stdout = std::io::stdout();
+        serde_json::to_writer_pretty(&stdout, &sites).map_err(|e| CaddyDevError::Stdio {
+            stream: "stdout",
+            source: e.into(),
+        })?;
+        println!();
+        return Ok(());
+    }
+
+    if sites.is_empty() {
+        println!("No sites found in the configured folders.");
+        return Ok(());
+    }
+
+    let rows: Vec<(&str, String, String)> = sites
+        .iter()
+        .map(|site| {
+            let upstreams = if site.upstreams.is_empty() {
+                "-".to_string()
+            } else {
+                site.upstreams.join(", ")
+            };
+            let file = format!("{}:{}", site.file.display(), site.line);
+            (site.address.as_str(), upstreams, file)
+        })
+        .collect();
+    let domain_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0).max(6);
+    let upstream_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max(8);
+
+    println!(
+        "{:<domain_width$}  {:<upstream_width$}  FILE",
+        "DOMAIN", "UPSTREAM"
+    );
+    for (domain, upstreams, file) in rows {
+        println!(
+            "{:<domain_width$}  {:<upstream_width$}  {}",
+            domain, upstreams, file
+        );
+    }
+    Ok(())
+}
+
+/// Report sites defined more than once across the imported files
+fn check_sites() -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    reload::check_overlapping_folders(&config)?;
+    let files = config.imported_files();
+    let (sites, unreadable) = sites::collect_sites(&files);
+    for (path, e) in &unreadable {
+        eprintln!("Warning: Cannot read '{}': {}", path.display(), e);
+    }
+
+    let duplicates = sites::find_duplicates(&sites);
+    if !duplicates.is_empty() {
+        return Err(CaddyDevError::DuplicateSites(duplicates));
+    }
+    println!(
+        "No duplicate sites: {} site(s) in {} file(s).",
+        sites.len(),
+        files.len()
+    );
+    Ok(())
+}
+
+/// Probe the reverse_proxy upstreams of every imported site, failing with
+/// `UpstreamsDown` (exit code 27) if any is down
+fn check_health(args: HealthArgs) -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let (sites, unreadable) = sites::collect_sites(&config.imported_files());
+    for (path, e) in &unreadable {
+        eprintln!("Warning: Cannot read '{}': {}", path.display(), e);
+    }
+
+    let timeout = std::time::Duration::from_millis(args.timeout);
+    let report = health::check(&sites, args.http.as_deref(), timeout);
+    if report.total == 0 {
+        println!("No reverse_proxy upstreams found in the configured folders.");
+        return Ok(());
+    }
+
+    let rows: Vec<(&str, &str, &str, &str)> = report
+        .upstreams
+        .iter()
+        .map(|checked| {
+            let (status, detail) = match &checked.health {
+                health::Health::Up(detail) => ("up", detail.as_str()),
+                health::Health::Down(reason) => ("down", reason.as_str()),
+                health::Health::Skipped(reason) => ("skipped", reason.as_str()),
+            };
+            (
+                status,
+                checked.site.as_str(),
+                checked.upstream.as_str(),
+                detail,
+            )
+        })
+        .collect();
+    let domain_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max(6);
+    let upstream_width = rows.iter().map(|row| row.2.len()).max().unwrap_or(0).max(8);
+
+    println!(
+        "{:<8} {:<domain_width$}  {:<upstream_width$}  DETAIL",
+        "STATUS", "DOMAIN", "UPSTREAM"
+    );
+    for (status, domain, upstream, detail) in &rows {
+        println!(
+            "{:<8} {:<domain_width$}  {:<upstream_width$}  {}",
+            status, domain, upstream, detail
         );
-        std::process::exit(1);
     }
 
     println!();
-    println!("Configuration saved to: {}", main_caddyfile_path.display());
-    println!("Imported {} folder(s).", folders.len());
-    println!("Run 'caddy-dev reload' to apply the configuration.");
+    println!("{} of {} upstream(s) down.", report.down, report.total);
+    if report.down > 0 {
+        return Err(CaddyDevError::UpstreamsDown {
+            down: report.down,
+            total: report.total,
+        });
+    }
+    Ok(())
 }
 
-/// Reload Caddy with the generated config
-fn reload_caddy() {
+/// Run every diagnostic and print pass/warn/fail with hints, failing if any check failed
+fn run_doctor() -> Result<(), CaddyDevError> {
+    let config_dir = get_config_dir();
+    let config_path = get_config_path();
     let main_caddyfile_path = get_main_caddyfile_path();
 
-    if !main_caddyfile_path.exists() {
-        eprintln!(
-            "Error: Configuration file not found at '{}'",
-            main_caddyfile_path.display()
+    let mut checks = vec![doctor::check_config_dir(&config_dir)];
+    let (config_check, config) = doctor::check_config_file(&config_path, &main_caddyfile_path);
+    checks.push(config_check);
+    checks.push(doctor::check_main_caddyfile(&main_caddyfile_path));
+    checks.push(doctor::check_caddy_binary(&config.caddy_bin, &config_path));
+    checks.push(doctor::check_admin_endpoint(&config.admin, &config_path));
+    checks.extend(doctor::check_folders(&config));
+    checks.push(doctor::check_local_ca());
+
+    for check in &checks {
+        let label = match check.status {
+            doctor::Status::Pass => console::style("PASS").green(),
+            doctor::Status::Warn => console::style("WARN").yellow(),
+            doctor::Status::Fail => console::style("FAIL").red().bold(),
+        };
+        println!("{} {}: {}", label, check.name, check.message);
+        if let Some(hint) = &check.hint {
+            println!("     → {}", hint);
+        }
+    }
+
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    let failed = count(doctor::Status::Fail);
+    println!();
+    println!(
+        "{} passed, {} warning(s), {} failed",
+        count(doctor::Status::Pass),
+        count(doctor::Status::Warn),
+        failed
+    );
+    if failed > 0 {
+        return Err(CaddyDevError::ChecksFailed { failed });
+    }
+    Ok(())
+}
+
+/// Report the projects under a directory tree, which templates need generating and which
+/// projects aren't imported, adding their folders with `--add`
+fn scan_projects(args: ScanArgs) -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let root = PathBuf::from(normalize_folder(&args.root.to_string_lossy()));
+    let projects = scan::scan(&root, args.max_depth, &config)?;
+    if projects.is_empty() {
+        println!("No projects found under {}.", root.display());
+        return Ok(());
+    }
+
+    println!("{:<14} {:<9} PROJECT", "STATUS", "IMPORTED");
+    for project in &projects {
+        let status = match project.state {
+            scan::ProjectState::UpToDate => "up to date",
+            scan::ProjectState::Stale => "stale",
+            scan::ProjectState::NotGenerated => "not generated",
+            scan::ProjectState::NoTemplate => "no template",
+        };
+        let imported = if project.imported_by.is_some() {
+            "yes"
+        } else {
+            "no"
+        };
+        println!("{:<14} {:<9} {}", status, imported, project.dir.display());
+    }
+
+    let count = |state| projects.iter().filter(|p| p.state == state).count();
+    let stale = count(scan::ProjectState::Stale);
+    let not_generated = count(scan::ProjectState::NotGenerated);
+    println!();
+    println!(
+        "{} project(s): {} not generated, {} stale.",
+        projects.len(),
+        not_generated,
+        stale
+    );
+    if stale + not_generated > 0 {
+        println!("Generate them with: caddy-dev generate --output-dir <PROJECT>");
+    }
+
+    if projects.iter().all(|project| project.imported_by.is_some()) {
+        return Ok(());
+    }
+    if !args.add {
+        println!(
+            "Import the projects that aren't imported yet with: caddy-dev scan {} --add",
+            root.display()
         );
-        eprintln!("Run 'caddy-dev init' first to set up the configuration.");
-        std::process::exit(1);
+        return Ok(());
+    }
+    for folder in scan::add_projects(&root, &projects)? {
+        println!("Added: {}", folder);
+    }
+    println!("Run 'caddy-dev reload' to apply the configuration.");
+    Ok(())
+}
+
+/// Watch templates, vars files and imported Caddyfile.dev files, regenerating and
+/// reloading on changes
+fn watch_caddy(args: WatchArgs) -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let debounce = std::time::Duration::from_millis(args.debounce);
+    watch::watch_projects(config, debounce, !args.no_reload, print_watch_event)
+}
+
+/// Report what `watch` and `run` are doing
+fn print_watch_event(event: watch::WatchEvent) {
+    match event {
+        watch::WatchEvent::Started { projects, folders } => println!(
+            "Watching {} project(s) from {} folder(s). Press Ctrl-C to stop.",
+            projects, folders
+        ),
+        watch::WatchEvent::ConfigReloaded(path) => println!("✔ Reloaded {}", path.display()),
+        watch::WatchEvent::Generated(generated) => {
+            print_warnings(&generated.warnings);
+            println!("✔ Generated {}", generated.output_path.display());
+        }
+        watch::WatchEvent::GenerateFailed { dir, error } => {
+            print_warnings(error.warnings());
+            eprintln!("✘ {}\n{}", dir.display(), error)
+        }
+        watch::WatchEvent::Reloaded(warnings) => {
+            print_warnings(&warnings);
+            println!("✔ Caddy reloaded");
+        }
+        watch::WatchEvent::Failed(error) => eprintln!("✘ {}", error),
     }
+}
 
+/// Print each line Caddy writes to `output`, prettified unless `raw`
+fn stream_logs(
+    output: impl std::io::Read + Send + 'static,
+    raw: bool,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in std::io::BufReader::new(output)
+            .lines()
+            .map_while(Result::ok)
+        {
+            if raw {
+                println!("{}", line);
+            } else {
+                println!("{}", logs::prettify(&line));
+            }
+        }
+    })
+}
+
+/// Run Caddy in the foreground with the main Caddyfile, streaming its logs and reloading
+/// it when projects change, until Ctrl-C, SIGTERM or SIGHUP
+fn run_caddy(args: RunArgs) -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let main_caddyfile_path = prepare_main_caddyfile(&config)?;
+
+    if reload::caddy_is_running(&config) {
+        return Err(CaddyDevError::CaddyAlreadyRunning {
+            address: config.admin,
+        });
+    }
+
+    let mut child = process::caddy_run_command(&config.caddy_bin, &main_caddyfile_path)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|source| CaddyDevError::CaddyNotFound {
+            bin: config.caddy_bin.clone(),
+            source,
+        })?;
+    let pid = child.id();
+    let pid_path = get_config_dir().join(process::PID_FILE_NAME);
+    if let Err(e) = process::write_pid(&pid_path, pid) {
+        eprintln!("Warning: Cannot write '{}': {}", pid_path.display(), e);
+    }
     println!(
-        "Reloading Caddy with config: {}",
+        "Caddy running (pid {}) with config: {}",
+        pid,
         main_caddyfile_path.display()
     );
 
-    // Execute caddy reload
-    let status = std::process::Command::new("caddy")
-        .args(&["reload", "--config", main_caddyfile_path.to_str().unwrap()])
-        .status()
-        .expect("Failed to execute 'caddy reload'");
+    let mut streams = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        streams.push(stream_logs(stdout, args.raw_logs));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        streams.push(stream_logs(stderr, args.raw_logs));
+    }
 
-    if status.success() {
-        println!("Caddy successfully reloaded!");
-    } else {
+    let (events, received) = std::sync::mpsc::channel();
+    let interrupted = events.clone();
+    if let Err(e) = ctrlc::set_handler(move || {
+        let _ = interrupted.send(process::RunEvent::Interrupted);
+    }) {
         eprintln!(
-            "Error: Caddy reload failed with exit code: {:?}",
-            status.code()
+            "Warning: Cannot handle Ctrl-C and termination signals: {}",
+            e
         );
-        std::process::exit(1);
     }
+    let watch_failed = events.clone();
+    std::thread::spawn(move || {
+        let _ = events.send(process::RunEvent::Exited(child.wait()));
+    });
+
+    let debounce = std::time::Duration::from_millis(args.debounce);
+    std::thread::spawn(move || {
+        if let Err(e) = watch::watch_projects(config, debounce, true, print_watch_event) {
+            let _ = watch_failed.send(process::RunEvent::WatchFailed(e));
+        }
+    });
+
+    let end = process::supervise(pid, &received, streams, |event| match event {
+        process::StopEvent::Stopping => println!("Stopping Caddy..."),
+        process::StopEvent::TerminateFailed(e) => {
+            eprintln!("Error: Cannot stop Caddy (pid {}): {}", pid, e)
+        }
+        process::StopEvent::Killing => eprintln!(
+            "Warning: Caddy (pid {}) is still shutting down; killing it",
+            pid
+        ),
+        process::StopEvent::KillFailed(e) => {
+            eprintln!("Error: Cannot kill Caddy (pid {}): {}", pid, e)
+        }
+    });
+    let _ = fs::remove_file(&pid_path);
+    match end? {
+        process::RunEnd::Stopped => println!("Caddy stopped."),
+        process::RunEnd::Exited => println!("Caddy exited."),
+    }
+    Ok(())
 }
 
-fn main() {
-    let args = Args::parse();
+/// Start Caddy in the background with the main Caddyfile
+fn start_caddy() -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let main_caddyfile_path = prepare_main_caddyfile(&config)?;
+    start_in_background(&config, &main_caddyfile_path)
+}
+
+/// Start Caddy in the background and report where to find it
+fn start_in_background(config: &Config, main_caddyfile_path: &Path) -> Result<(), CaddyDevError> {
+    let started = process::start(config, main_caddyfile_path)?;
+    print_warnings(&started.warnings);
+    println!("Caddy started (pid {})", started.pid);
+    println!("Config: {}", main_caddyfile_path.display());
+    println!("Admin endpoint: {}", config.admin);
+    println!("Logs: {}", started.log_path.display());
+    Ok(())
+}
 
-    match args.command {
-        Command::Generate {
-            output_dir,
-            template,
-            variables,
-        } => {
-            generate_caddyfile_dev(output_dir, template, variables);
+/// Stop Caddy through the admin API, or by signaling the process started by `caddy-dev start`
+fn stop_caddy() -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    match process::stop(&config)? {
+        process::Stopped::NotRunning => println!("Caddy is not running."),
+        process::Stopped::Stopped => println!("Caddy stopped."),
+        process::Stopped::StillRunning(pid) => {
+            eprintln!("Warning: Caddy (pid {}) is still shutting down", pid);
+            println!("Caddy stopped.");
         }
-        Command::Init => {
-            init_caddydev();
+    }
+    Ok(())
+}
+
+/// Report whether Caddy is running, its admin endpoint and the config it loaded, failing
+/// when Caddy doesn't answer
+fn caddy_status() -> Result<(), CaddyDevError> {
+    let config = load_config()?;
+    let status = process::status(&config);
+
+    match (&status.loaded, status.pid) {
+        (Ok(_), Some(pid)) => println!("Caddy is running (pid {}, started by caddy-dev)", pid),
+        (Ok(_), None) => println!("Caddy is running (not started by caddy-dev)"),
+        (Err(_), Some(pid)) => println!(
+            "Caddy is running (pid {}), but its admin API doesn't answer",
+            pid
+        ),
+        (Err(_), None) => println!("Caddy is not running"),
+    }
+
+    match &status.loaded {
+        Ok(_) => println!("Admin endpoint: {}", config.admin),
+        Err(e) => println!("Admin endpoint: {} ({})", config.admin, e),
+    }
+
+    if status.loaded.is_ok() {
+        let main_caddyfile_path = get_main_caddyfile_path();
+        match status.up_to_date {
+            Some(true) => println!("Config: {} (up to date)", main_caddyfile_path.display()),
+            Some(false) => println!(
+                "Config: differs from {}; apply it with: caddy-dev reload",
+                main_caddyfile_path.display()
+            ),
+            None => println!("Config: loaded, not from caddy-dev's main Caddyfile"),
         }
-        Command::Reload => {
-            reload_caddy();
+
+        if status.hosts.is_empty() {
+            println!("Sites: none");
+        } else {
+            println!("Sites: {}", status.hosts.join(", "));
         }
     }
+
+    if status.pid.is_some() {
+        println!(
+            "Logs: {}",
+            get_config_dir().join(process::LOG_FILE_NAME).display()
+        );
+    }
+
+    if status.loaded.is_err() {
+        return Err(CaddyDevError::CaddyNotRunning {
+            address: config.admin,
+        });
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let result = match args.command {
+        Command::Generate(args) => generate_caddyfile_dev(args),
+        Command::Init(args) => init_caddydev(args),
+        Command::Reload(args) => reload_caddy(args),
+        Command::Folders { command } => manage_folders(command),
+        Command::Validate => validate_caddy(),
+        Command::Sites(args) => list_sites(args),
+        Command::Check => check_sites(),
+        Command::Health(args) => check_health(args),
+        Command::Doctor => run_doctor(),
+        Command::Scan(args) => scan_projects(args),
+        Command::Watch(args) => watch_caddy(args),
+        Command::Run(args) => run_caddy(args),
+        Command::Start => start_caddy(),
+        Command::Stop => stop_caddy(),
+        Command::Status => caddy_status(),
+        Command::Config { command } => show_config(command),
+        Command::Port { command } => manage_ports(command),
+        Command::Vars { command } => manage_vars(command),
+    };
+
+    if let Err(error) = result {
+        fail(&error);
+    }
 }
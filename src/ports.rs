@@ -0,0 +1,258 @@
+// src/ports.rs
+//! Persistent registry of TCP ports allocated to projects for `{{port:name}}` placeholders.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::TcpListener;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::error::CaddyDevError;
+
+/// Prefix of port placeholders (`{{port:web}}`)
+pub const PORT_PREFIX: &str = "port:";
+
+/// Registry file name inside the config directory
+pub const REGISTRY_FILE_NAME: &str = "ports.toml";
+
+/// Ports handed out by the allocator
+pub const PORT_RANGE: RangeInclusive<u16> = 10000..=19999;
+
+/// A port reserved for a named service of a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortEntry {
+    pub project: String,
+    pub name: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    #[serde(default, rename = "port")]
+    ports: Vec<PortEntry>,
+}
+
+/// Port registry stored as TOML in the config directory
+#[derive(Debug)]
+pub struct PortRegistry {
+    path: PathBuf,
+    entries: Vec<PortEntry>,
+}
+
+impl PortRegistry {
+    /// Load the registry, starting empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, CaddyDevError> {
+        let error = |message: String| CaddyDevError::PortRegistry {
+            path: path.to_path_buf(),
+            message,
+        };
+        let entries = match fs::read_to_string(path) {
+            Ok(content) => {
+                toml::from_str::<RegistryFile>(&content)
+                    .map_err(|e| error(e.to_string()))?
+                    .ports
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(error(e.to_string())),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    /// Write the registry back to disk
+    pub fn save(&self) -> Result<(), CaddyDevError> {
+        let error = |message: String| CaddyDevError::PortRegistry {
+            path: self.path.clone(),
+            message,
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| error(e.to_string()))?;
+        }
+        let file = RegistryFile {
+            ports: self.entries.clone(),
+        };
+        let content = toml::to_string(&file).map_err(|e| error(e.to_string()))?;
+        fs::write(
+            &self.path,
+            format!(
+                "# Managed by caddy-dev, edit with 'caddy-dev port'\n\n{}",
+                content
+            ),
+        )
+        .map_err(|e| error(e.to_string()))
+    }
+
+    pub fn entries(&self) -> &[PortEntry] {
+        &self.entries
+    }
+
+    /// Port already allocated to `name` in `project`
+    pub fn get(&self, project: &str, name: &str) -> Option<u16> {
+        self.entries
+            .iter()
+            .find(|e| e.project == project && e.name == name)
+            .map(|e| e.port)
+    }
+
+    /// Return the port allocated to `name` in `project`, allocating a free one if needed.
+    /// The boolean is `true` when a new port was allocated.
+    pub fn allocate(&mut self, project: &str, name: &str) -> Result<(u16, bool), CaddyDevError> {
+        if let Some(port) = self.get(project, name) {
+            return Ok((port, false));
+        }
+        let port = PORT_RANGE
+            .filter(|port| !self.entries.iter().any(|e| e.port == *port))
+            .find(|port| is_port_free(*port))
+            .ok_or_else(|| CaddyDevError::PortUnavailable {
+                name: name.to_string(),
+                message: format!(
+                    "no free port left in range {}-{}",
+                    PORT_RANGE.start(),
+                    PORT_RANGE.end()
+                ),
+            })?;
+        self.entries.push(PortEntry {
+            project: project.to_string(),
+            name: name.to_string(),
+            port,
+        });
+        Ok((port, true))
+    }
+
+    /// Remove the allocation of `name` in `project`, returning its port
+    pub fn release(&mut self, project: &str, name: &str) -> Option<u16> {
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.project == project && e.name == name)?;
+        Some(self.entries.remove(index).port)
+    }
+}
+
+/// Key identifying a project in the registry: its canonical directory path
+pub fn project_key(dir: &Path) -> String {
+    let dir = dir.canonicalize().unwrap_or_else(|_| {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(dir))
+            .unwrap_or_else(|_| dir.to_path_buf())
+    });
+    dir.to_string_lossy().into_owned()
+}
+
+/// Whether no process is listening on `port` (checked on both loopback and all interfaces)
+pub fn is_port_free(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok() && TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Registry path in an empty directory under the system temp dir, unique to the test
+    fn registry_path(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("caddy-dev-ports-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(REGISTRY_FILE_NAME)
+    }
+
+    #[test]
+    fn missing_file_loads_as_an_empty_registry() {
+        let path = registry_path("missing");
+        let registry = PortRegistry::load(&path).unwrap();
+        assert!(registry.entries().is_empty());
+
+        registry.save().unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# Managed by caddy-dev"), "{}", content);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn invalid_file_is_a_registry_error() {
+        let path = registry_path("invalid");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "[[port]]\nport = \"x\"\n").unwrap();
+
+        match PortRegistry::load(&path) {
+            Err(CaddyDevError::PortRegistry { path: got, .. }) => assert_eq!(got, path),
+            other => panic!("expected a registry error, got {:?}", other),
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn allocation_is_stable_across_save_and_load() {
+        let path = registry_path("stable");
+        let mut registry = PortRegistry::load(&path).unwrap();
+
+        let (web, allocated) = registry.allocate("/src/app", "web").unwrap();
+        assert!(allocated);
+        assert!(PORT_RANGE.contains(&web));
+        assert_eq!(registry.allocate("/src/app", "web").unwrap(), (web, false));
+        let (api, _) = registry.allocate("/src/app", "api").unwrap();
+        let (other, _) = registry.allocate("/src/other", "web").unwrap();
+        assert_ne!(api, web);
+        assert_ne!(other, web);
+        assert_ne!(other, api);
+        registry.save().unwrap();
+
+        let mut registry = PortRegistry::load(&path).unwrap();
+        assert_eq!(registry.entries().len(), 3);
+        assert_eq!(registry.allocate("/src/app", "web").unwrap(), (web, false));
+        assert_eq!(registry.get("/src/app", "api"), Some(api));
+        assert_eq!(registry.get("/src/other", "web"), Some(other));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn allocation_skips_ports_in_use() {
+        let path = registry_path("in-use");
+        let mut registry = PortRegistry::load(&path).unwrap();
+        let first = PORT_RANGE.clone().find(|port| is_port_free(*port)).unwrap();
+        let _listener = TcpListener::bind(("127.0.0.1", first)).unwrap();
+
+        let (port, _) = registry.allocate("/src/app", "web").unwrap();
+
+        assert_ne!(port, first);
+        assert!(port > first);
+    }
+
+    #[test]
+    fn allocation_skips_registered_ports() {
+        let path = registry_path("registered");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let taken = PORT_RANGE.clone().find(|port| is_port_free(*port)).unwrap();
+        fs::write(
+            &path,
+            format!(
+                "[[port]]\nproject = \"/src/old\"\nname = \"web\"\nport = {}\n",
+                taken
+            ),
+        )
+        .unwrap();
+        let mut registry = PortRegistry::load(&path).unwrap();
+
+        let (port, allocated) = registry.allocate("/src/app", "web").unwrap();
+
+        assert!(allocated);
+        assert_ne!(port, taken);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn release_removes_the_allocation() {
+        let path = registry_path("release");
+        let mut registry = PortRegistry::load(&path).unwrap();
+        let (port, _) = registry.allocate("/src/app", "web").unwrap();
+        registry.allocate("/src/app", "api").unwrap();
+
+        assert_eq!(registry.release("/src/app", "web"), Some(port));
+        assert_eq!(registry.release("/src/app", "web"), None);
+        assert_eq!(registry.release("/src/other", "api"), None);
+        assert_eq!(registry.get("/src/app", "web"), None);
+        assert_eq!(registry.entries().len(), 1);
+    }
+}
@@ -0,0 +1,573 @@
+// src/process.rs
+//! The background Caddy process started by `caddy-dev start`: pidfile, log file and signals.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::admin::{self, AdminClient, AdminError};
+use crate::config::Config;
+use crate::error::CaddyDevError;
+use crate::store;
+
+/// Pidfile of the background Caddy, in the config directory
+pub const PID_FILE_NAME: &str = "caddy.pid";
+
+/// Log file of the background Caddy, in the config directory
+pub const LOG_FILE_NAME: &str = "caddy.log";
+
+/// `caddy run` with a Caddyfile, in a process group of its own so Ctrl-C in the
+/// terminal doesn't reach Caddy
+pub fn caddy_run_command(caddy_bin: &str, caddyfile: &Path) -> Command {
+    let mut command = Command::new(caddy_bin);
+    command
+        .arg("run")
+        .arg("--config")
+        .arg(caddyfile)
+        .args(["--adapter", "caddyfile"])
+        .stdin(Stdio::null());
+
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    command
+}
+
+/// Spawn `caddy run` with a Caddyfile, detached from the terminal and appending its
+/// output to `log_path`
+pub fn spawn_caddy(caddy_bin: &str, caddyfile: &Path, log_path: &Path) -> io::Result<Child> {
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+
+    caddy_run_command(caddy_bin, caddyfile)
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()
+}
+
+/// Read the pid recorded in a pidfile
+pub fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Record a pid in a pidfile
+pub fn write_pid(path: &Path, pid: u32) -> io::Result<()> {
+    fs::write(path, format!("{}\n", pid))
+}
+
+/// Whether a process with this pid exists
+#[cfg(unix)]
+pub fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks that the process exists and may be signaled
+    let signaled = unsafe { libc::kill(pid, 0) } == 0;
+    signaled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Whether a process with this pid exists
+#[cfg(not(unix))]
+pub fn is_alive(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+}
+
+/// Arguments of a running process, `None` if they can't be read
+#[cfg(unix)]
+fn command_line(pid: u32) -> Option<Vec<String>> {
+    if let Ok(cmdline) = fs::read(format!("/proc/{}/cmdline", pid)) {
+        return Some(
+            String::from_utf8_lossy(&cmdline)
+                .split('\0')
+                .filter(|arg| !arg.is_empty())
+                .map(String::from)
+                .collect(),
+        );
+    }
+    // No procfs, e.g. on macOS
+    let output = Command::new("ps")
+        .args(["-o", "command=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let command = String::from_utf8_lossy(&output.stdout);
+    (output.status.success() && !command.trim().is_empty())
+        .then(|| command.split_whitespace().map(String::from).collect())
+}
+
+/// Whether process arguments are those of `caddy_bin run`, possibly run by an interpreter
+fn is_caddy_run(args: &[String], caddy_bin: &str) -> bool {
+    let bin_name = Path::new(caddy_bin).file_name();
+    args.iter()
+        .take(2)
+        .any(|arg| arg == caddy_bin || Path::new(arg).file_name() == bin_name)
+        && args.iter().any(|arg| arg == "run")
+}
+
+/// Whether this pid is a running `caddy_bin run`, rather than an unrelated process that
+/// reused the pid of a Caddy that exited (e.g. before a reboot)
+#[cfg(unix)]
+pub fn is_caddy(pid: u32, caddy_bin: &str) -> bool {
+    is_alive(pid) && command_line(pid).is_some_and(|args| is_caddy_run(&args, caddy_bin))
+}
+
+/// Whether this pid is a running `caddy_bin run`, rather than an unrelated process that
+/// reused the pid of a Caddy that exited (e.g. before a reboot)
+#[cfg(not(unix))]
+pub fn is_caddy(pid: u32, _caddy_bin: &str) -> bool {
+    is_alive(pid)
+}
+
+/// Pid of the Caddy started by caddy-dev, if it's still running. A stale pidfile is removed.
+pub fn started_pid(config: &Config) -> Option<u32> {
+    let pid_path = store::get_config_dir().join(PID_FILE_NAME);
+    let pid = read_pid(&pid_path)?;
+    if is_caddy(pid, &config.caddy_bin) {
+        Some(pid)
+    } else {
+        let _ = fs::remove_file(&pid_path);
+        None
+    }
+}
+
+/// Ask a process to terminate
+#[cfg(unix)]
+pub fn terminate(pid: u32) -> io::Result<()> {
+    let pid = libc::pid_t::try_from(pid)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid pid"))?;
+    if unsafe { libc::kill(pid, libc::SIGTERM) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Ask a process to terminate
+#[cfg(not(unix))]
+pub fn terminate(pid: u32) -> io::Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/PID", &pid.to_string()])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("taskkill failed with {}", status)))
+    }
+}
+
+/// Kill a process that doesn't terminate
+#[cfg(unix)]
+pub fn kill(pid: u32) -> io::Result<()> {
+    let pid = libc::pid_t::try_from(pid)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid pid"))?;
+    if unsafe { libc::kill(pid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Kill a process that doesn't terminate
+#[cfg(not(unix))]
+pub fn kill(pid: u32) -> io::Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/F", "/PID", &pid.to_string()])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("taskkill failed with {}", status)))
+    }
+}
+
+/// Last `count` lines of a file, among those written after byte `offset`
+pub fn tail(path: &Path, offset: u64, count: usize) -> Vec<String> {
+    let bytes = fs::read(path).unwrap_or_default();
+    let start = usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .min(bytes.len());
+    let content = String::from_utf8_lossy(&bytes[start..]);
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// A Caddy started in the background
+#[derive(Debug)]
+pub struct Started {
+    pub pid: u32,
+    pub log_path: PathBuf,
+    /// Problems that didn't prevent Caddy from starting
+    pub warnings: Vec<String>,
+}
+
+/// Spawn `caddy run` in the background with a pidfile and a log file in the config
+/// directory, and wait until its admin API answers
+pub fn start(config: &Config, main_caddyfile_path: &Path) -> Result<Started, CaddyDevError> {
+    let client = AdminClient::new(&config.admin);
+    if client.config().is_ok() {
+        return Err(CaddyDevError::CaddyAlreadyRunning {
+            address: config.admin.clone(),
+        });
+    }
+
+    let config_dir = store::get_config_dir();
+    let pid_path = config_dir.join(PID_FILE_NAME);
+    let log_path = config_dir.join(LOG_FILE_NAME);
+    let log_offset = fs::metadata(&log_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut child =
+        spawn_caddy(&config.caddy_bin, main_caddyfile_path, &log_path).map_err(|source| {
+            CaddyDevError::CaddyNotFound {
+                bin: config.caddy_bin.clone(),
+                source,
+            }
+        })?;
+    let pid = child.id();
+    let mut warnings = Vec::new();
+    if let Err(e) = write_pid(&pid_path, pid) {
+        warnings.push(format!("Cannot write '{}': {}", pid_path.display(), e));
+    }
+
+    // Caddy may take a moment to start, or exit right away on a bad config or a busy port
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            let _ = fs::remove_file(&pid_path);
+            return Err(CaddyDevError::StartFailed {
+                status: status.to_string(),
+                log: tail(&log_path, log_offset, 10),
+                log_path,
+            });
+        }
+        if client.config().is_ok() {
+            break;
+        }
+        if Instant::now() >= deadline {
+            warnings.push(format!(
+                "Caddy is not answering at '{}' yet; see {}",
+                config.admin,
+                log_path.display()
+            ));
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    Ok(Started {
+        pid,
+        log_path,
+        warnings,
+    })
+}
+
+/// Something that happened while Caddy runs in the foreground, see [`supervise`]
+#[derive(Debug)]
+pub enum RunEvent {
+    /// Ctrl-C, SIGTERM or SIGHUP
+    Interrupted,
+    /// Projects can no longer be watched
+    WatchFailed(CaddyDevError),
+    /// Caddy exited
+    Exited(io::Result<ExitStatus>),
+}
+
+/// What [`supervise`] does to Caddy, for reporting progress
+#[derive(Debug)]
+pub enum StopEvent {
+    /// Caddy was asked to shut down
+    Stopping,
+    /// Caddy could not be asked to shut down
+    TerminateFailed(io::Error),
+    /// Caddy didn't shut down in time and is being killed
+    Killing,
+    /// Caddy could not be killed
+    KillFailed(io::Error),
+}
+
+/// How a Caddy supervised by [`supervise`] ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunEnd {
+    /// Caddy shut down when asked to
+    Stopped,
+    /// Caddy exited on its own, successfully
+    Exited,
+}
+
+/// Wait for the Caddy with this pid to exit, asking it to shut down gracefully on
+/// [`RunEvent::Interrupted`] or when watching fails, and killing it if it doesn't within
+/// 10 seconds. `logs` are the threads forwarding its output, joined before returning so
+/// its last lines get through.
+pub fn supervise(
+    pid: u32,
+    events: &Receiver<RunEvent>,
+    logs: Vec<JoinHandle<()>>,
+    mut on_event: impl FnMut(StopEvent),
+) -> Result<RunEnd, CaddyDevError> {
+    let mut stopping = false;
+    let mut killed = false;
+    let mut failure = None;
+    let status = loop {
+        let event = if stopping {
+            events.recv_timeout(Duration::from_secs(10)).ok()
+        } else {
+            events.recv().ok()
+        };
+        match event {
+            Some(RunEvent::Interrupted) if !stopping => {
+                ask_to_stop(pid, &mut on_event);
+                stopping = true;
+            }
+            Some(RunEvent::Interrupted) => {}
+            Some(RunEvent::WatchFailed(error)) => {
+                if !stopping {
+                    ask_to_stop(pid, &mut on_event);
+                    stopping = true;
+                }
+                failure = Some(error);
+            }
+            Some(RunEvent::Exited(status)) => break status,
+            None if !killed => {
+                on_event(StopEvent::Killing);
+                if let Err(e) = kill(pid) {
+                    on_event(StopEvent::KillFailed(e));
+                }
+                killed = true;
+            }
+            // Caddy's output may never end, so its logs aren't waited for
+            None => {
+                return Err(CaddyDevError::StopFailed {
+                    message: format!("pid {} did not exit", pid),
+                });
+            }
+        }
+    };
+
+    for log in logs {
+        let _ = log.join();
+    }
+    if let Some(error) = failure {
+        return Err(error);
+    }
+    match status {
+        Ok(_) if stopping => Ok(RunEnd::Stopped),
+        Ok(status) if status.success() => Ok(RunEnd::Exited),
+        Ok(status) => Err(CaddyDevError::CaddyExited {
+            status: status.to_string(),
+        }),
+        Err(e) => Err(CaddyDevError::StopFailed {
+            message: format!("cannot wait for pid {}: {}", pid, e),
+        }),
+    }
+}
+
+/// Ask Caddy to shut down for [`supervise`], which then waits for it to exit
+fn ask_to_stop(pid: u32, on_event: &mut impl FnMut(StopEvent)) {
+    on_event(StopEvent::Stopping);
+    if let Err(e) = terminate(pid) {
+        on_event(StopEvent::TerminateFailed(e));
+    }
+}
+
+/// State of Caddy reported by `caddy-dev status`
+#[derive(Debug)]
+pub struct Status {
+    /// Pid of the Caddy started by caddy-dev, if it's still running
+    pub pid: Option<u32>,
+    /// Configuration loaded in Caddy, or why the admin API doesn't answer
+    pub loaded: Result<serde_json::Value, AdminError>,
+    /// Whether the loaded configuration is the main Caddyfile adapted the same way,
+    /// `None` if Caddy doesn't answer or the main Caddyfile can't be adapted
+    pub up_to_date: Option<bool>,
+    /// Host names served by the loaded configuration
+    pub hosts: Vec<String>,
+}
+
+/// Ask Caddy for its configuration and compare it with the main Caddyfile
+pub fn status(config: &Config) -> Status {
+    let client = AdminClient::new(&config.admin);
+    let loaded = client.config();
+    let (up_to_date, hosts) = match &loaded {
+        Ok(loaded) => {
+            let up_to_date = fs::read_to_string(store::get_main_caddyfile_path())
+                .ok()
+                .and_then(|caddyfile| client.adapt_caddyfile(&caddyfile).ok())
+                .and_then(|adapted| serde_json::from_str::<serde_json::Value>(&adapted.config).ok())
+                .map(|adapted| &adapted == loaded);
+            (up_to_date, admin::config_hosts(loaded))
+        }
+        Err(_) => (None, Vec::new()),
+    };
+    Status {
+        pid: started_pid(config),
+        loaded,
+        up_to_date,
+        hosts,
+    }
+}
+
+/// What `stop` did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    /// Caddy wasn't running
+    NotRunning,
+    /// Caddy stopped
+    Stopped,
+    /// Caddy was asked to stop, but the process with this pid is still shutting down
+    StillRunning(u32),
+}
+
+/// Stop Caddy through the admin API, or by signaling the process started by `start`
+pub fn stop(config: &Config) -> Result<Stopped, CaddyDevError> {
+    let pid_path = store::get_config_dir().join(PID_FILE_NAME);
+    let pid = started_pid(config);
+
+    match AdminClient::new(&config.admin).stop() {
+        Ok(()) => {}
+        Err(AdminError::Connect { .. }) => match pid {
+            // The admin API may be disabled or on another address
+            Some(pid) => terminate(pid).map_err(|e| CaddyDevError::StopFailed {
+                message: format!("pid {}: {}", pid, e),
+            })?,
+            None => {
+                let _ = fs::remove_file(&pid_path);
+                return Ok(Stopped::NotRunning);
+            }
+        },
+        Err(e) => {
+            return Err(CaddyDevError::StopFailed {
+                message: e.to_string(),
+            });
+        }
+    }
+
+    let mut stopped = Stopped::Stopped;
+    if let Some(pid) = pid {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while is_alive(pid) {
+            if Instant::now() >= deadline {
+                stopped = Stopped::StillRunning(pid);
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+    let _ = fs::remove_file(&pid_path);
+    Ok(stopped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn is_caddy_run_matches_the_configured_binary() {
+        let with = |bin: &str| args(&[bin, "run", "--config", "/home/me/Caddyfile"]);
+        assert!(is_caddy_run(&with("caddy"), "caddy"));
+        assert!(is_caddy_run(&with("/usr/bin/caddy"), "caddy"));
+        assert!(is_caddy_run(&with("/opt/caddy/caddy"), "/opt/caddy/caddy"));
+        assert!(is_caddy_run(
+            &args(&["python3", "/tmp/fake-caddy", "run"]),
+            "/tmp/fake-caddy"
+        ));
+    }
+
+    #[test]
+    fn is_caddy_run_rejects_other_processes() {
+        assert!(!is_caddy_run(&args(&["caddy", "validate"]), "caddy"));
+        assert!(!is_caddy_run(&args(&["/usr/bin/vim", "run"]), "caddy"));
+        assert!(!is_caddy_run(&args(&["bash", "-c", "caddy run"]), "caddy"));
+        assert!(!is_caddy_run(&[], "caddy"));
+    }
+
+    /// Spawn a command whose exit is sent to the returned channel, as `caddy-dev run` does
+    #[cfg(unix)]
+    fn spawn_supervised(
+        program: &str,
+        args: &[&str],
+    ) -> (u32, std::sync::mpsc::Sender<RunEvent>, Receiver<RunEvent>) {
+        let mut child = Command::new(program).args(args).spawn().unwrap();
+        let pid = child.id();
+        let (events, received) = std::sync::mpsc::channel();
+        let exited = events.clone();
+        thread::spawn(move || {
+            let _ = exited.send(RunEvent::Exited(child.wait()));
+        });
+        (pid, events, received)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn supervise_stops_caddy_when_interrupted() {
+        let (pid, events, received) = spawn_supervised("sleep", &["30"]);
+        events.send(RunEvent::Interrupted).unwrap();
+        events.send(RunEvent::Interrupted).unwrap();
+
+        let mut reported = Vec::new();
+        let end = supervise(pid, &received, Vec::new(), |event| {
+            reported.push(format!("{:?}", event))
+        });
+
+        assert_eq!(end.unwrap(), RunEnd::Stopped);
+        assert_eq!(reported, ["Stopping"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn supervise_reports_how_caddy_exited_on_its_own() {
+        let (pid, _events, received) = spawn_supervised("true", &[]);
+        assert_eq!(
+            supervise(pid, &received, Vec::new(), |_| {}).unwrap(),
+            RunEnd::Exited
+        );
+
+        let (pid, _events, received) = spawn_supervised("false", &[]);
+        match supervise(pid, &received, Vec::new(), |_| {}) {
+            Err(CaddyDevError::CaddyExited { status }) => {
+                assert!(status.contains('1'), "{}", status)
+            }
+            other => panic!("expected Caddy exited, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn supervise_stops_caddy_and_fails_when_watching_fails() {
+        let (pid, events, received) = spawn_supervised("sleep", &["30"]);
+        events
+            .send(RunEvent::WatchFailed(CaddyDevError::Watch(
+                io::Error::other("too many watches"),
+            )))
+            .unwrap();
+
+        let mut reported = Vec::new();
+        let end = supervise(pid, &received, Vec::new(), |event| {
+            reported.push(format!("{:?}", event))
+        });
+
+        assert!(matches!(end, Err(CaddyDevError::Watch(_))), "{:?}", end);
+        assert_eq!(reported, ["Stopping"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn is_caddy_rejects_a_reused_pid() {
+        let pid = std::process::id();
+        assert!(is_alive(pid));
+        assert!(!is_caddy(pid, "caddy"));
+    }
+}
@@ -0,0 +1,306 @@
+// src/reload.rs
+//! Checking the main Caddyfile and loading it into the running Caddy.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::admin::{AdminClient, AdminError};
+use crate::config::{Config, ImportedFile, ReloadMethod};
+use crate::error::CaddyDevError;
+use crate::{sites, validate};
+
+/// Write the main Caddyfile rendered from `config` to `main_caddyfile_path`, check it and
+/// load it into the running Caddy. Returns the warnings of the Caddyfile adapter, or
+/// [`CaddyDevError::CaddyNotRunning`] when Caddy isn't running.
+pub fn apply(config: &Config, main_caddyfile_path: &Path) -> Result<Vec<String>, CaddyDevError> {
+    fs::write(main_caddyfile_path, config.render_caddyfile()).map_err(|source| {
+        CaddyDevError::Io {
+            path: main_caddyfile_path.to_path_buf(),
+            source,
+        }
+    })?;
+    check_overlapping_folders(config)?;
+    check_duplicate_sites(&config.imported_files())?;
+    check_before_reload(config, main_caddyfile_path)?;
+    load_if_running(config, main_caddyfile_path)
+}
+
+/// Load the main Caddyfile into Caddy as it is, failing with
+/// [`CaddyDevError::CaddyNotRunning`] when Caddy isn't running
+pub fn load_if_running(
+    config: &Config,
+    main_caddyfile_path: &Path,
+) -> Result<Vec<String>, CaddyDevError> {
+    if !caddy_is_running(config) {
+        return Err(CaddyDevError::CaddyNotRunning {
+            address: config.admin.clone(),
+        });
+    }
+    load_into_caddy(config, main_caddyfile_path)
+}
+
+/// Format a Caddy error along with the files and lines it points to
+pub fn caddy_error_report(config: &Config, main_caddyfile_path: &Path, message: &str) -> String {
+    let mut report = message.trim_end().to_string();
+    let locations = validate::locate(message);
+    if let Some((innermost, import_chain)) = locations.split_first() {
+        report.push_str(&format!(
+            "\n\nCaused by {}",
+            validate::describe(config, main_caddyfile_path, innermost)
+        ));
+        for location in import_chain {
+            report.push_str(&format!(
+                "\nImported by {}",
+                validate::describe(config, main_caddyfile_path, location)
+            ));
+        }
+    }
+    report
+}
+
+/// Whether Caddy's admin endpoint answers
+pub fn caddy_is_running(config: &Config) -> bool {
+    !matches!(
+        AdminClient::new(&config.admin).config(),
+        Err(AdminError::Connect { .. })
+    )
+}
+
+/// Rewrite the main Caddyfile with explicit imports of the Caddyfile.dev files that are
+/// valid on their own, and return the broken ones
+pub fn exclude_broken_files(
+    config: &Config,
+    main_caddyfile_path: &Path,
+) -> Result<Vec<validate::BrokenFile>, CaddyDevError> {
+    let (valid, broken) =
+        validate::check_each_file(config, config.imported_files()).map_err(|source| {
+            CaddyDevError::CaddyNotFound {
+                bin: config.caddy_bin.clone(),
+                source,
+            }
+        })?;
+
+    let skipped: Vec<PathBuf> = broken.iter().map(|b| b.file.path.clone()).collect();
+    let content = config.render_caddyfile_with_files(&valid, &skipped);
+    fs::write(main_caddyfile_path, content).map_err(|source| CaddyDevError::Io {
+        path: main_caddyfile_path.to_path_buf(),
+        source,
+    })?;
+    Ok(broken)
+}
+
+/// Fail when imported files define the same site more than once, naming the conflicting
+/// files, before Caddy rejects the whole configuration as ambiguous
+pub fn check_duplicate_sites(files: &[ImportedFile]) -> Result<(), CaddyDevError> {
+    let (sites, _) = sites::collect_sites(files);
+    let duplicates = sites::find_duplicates(&sites);
+    if duplicates.is_empty() {
+        Ok(())
+    } else {
+        Err(CaddyDevError::DuplicateSites(duplicates))
+    }
+}
+
+/// Fail when several folders match the same file, which Caddy would then import (and
+/// define its sites) more than once
+pub fn check_overlapping_folders(config: &Config) -> Result<(), CaddyDevError> {
+    let overlaps = config.overlapping_files();
+    if overlaps.is_empty() {
+        Ok(())
+    } else {
+        Err(CaddyDevError::OverlappingFolders(overlaps))
+    }
+}
+
+/// Catch errors before touching the running config, and point to the file that caused them.
+/// Without a caddy binary, the admin API still rejects invalid configs atomically.
+pub fn check_before_reload(
+    config: &Config,
+    main_caddyfile_path: &Path,
+) -> Result<(), CaddyDevError> {
+    match validate::run_caddy_validate(&config.caddy_bin, main_caddyfile_path) {
+        validate::Validation::Invalid(message) => Err(CaddyDevError::InvalidCaddyfile {
+            report: caddy_error_report(config, main_caddyfile_path, &message),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Load the main Caddyfile into the running Caddy with the configured reload method.
+/// Returns the warnings of the Caddyfile adapter.
+pub fn load_into_caddy(
+    config: &Config,
+    main_caddyfile_path: &Path,
+) -> Result<Vec<String>, CaddyDevError> {
+    if config.reload == ReloadMethod::Cli {
+        reload_caddy_cli(config, main_caddyfile_path).map(|()| Vec::new())
+    } else {
+        reload_caddy_api(config, main_caddyfile_path)
+    }
+}
+
+/// Reload through the admin API: adapt the Caddyfile, then load the resulting JSON
+fn reload_caddy_api(
+    config: &Config,
+    main_caddyfile_path: &Path,
+) -> Result<Vec<String>, CaddyDevError> {
+    let caddyfile =
+        fs::read_to_string(main_caddyfile_path).map_err(|source| CaddyDevError::Io {
+            path: main_caddyfile_path.to_path_buf(),
+            source,
+        })?;
+
+    let client = AdminClient::new(&config.admin);
+    let result = client
+        .adapt_caddyfile(&caddyfile)
+        .and_then(|adapted| client.load(&adapted.config).map(|()| adapted.warnings));
+
+    result.map_err(|e| match e {
+        AdminError::Api { status, body } => CaddyDevError::ReloadFailed {
+            message: format!(
+                "Caddy admin API returned HTTP {}:\n{}",
+                status,
+                caddy_error_report(config, main_caddyfile_path, &body)
+            ),
+        },
+        AdminError::Connect { address, .. } => CaddyDevError::CaddyNotRunning { address },
+        e => CaddyDevError::ReloadFailed {
+            message: e.to_string(),
+        },
+    })
+}
+
+/// Reload by running `caddy reload`
+fn reload_caddy_cli(config: &Config, main_caddyfile_path: &Path) -> Result<(), CaddyDevError> {
+    let status = Command::new(&config.caddy_bin)
+        .arg("reload")
+        .arg("--config")
+        .arg(main_caddyfile_path)
+        .args(["--address", &config.admin])
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(CaddyDevError::ReloadFailed {
+            message: format!("'{} reload' exited with {}", config.caddy_bin, status),
+        }),
+        Err(source) => Err(CaddyDevError::CaddyNotFound {
+            bin: config.caddy_bin.clone(),
+            source,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_host_in_two_files_is_a_duplicate() {
+        let dir = std::env::temp_dir().join(format!(
+            "caddy-dev-reload-duplicates-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let mut files = Vec::new();
+        for (project, content) in [
+            ("a", "app.localhost {\n\treverse_proxy localhost:3000\n}\n"),
+            ("b", "other.localhost, app.localhost:443 {\n}\n"),
+        ] {
+            let path = dir.join(project).join("Caddyfile.dev");
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            files.push(ImportedFile {
+                folder: dir.to_string_lossy().into_owned(),
+                path,
+            });
+        }
+
+        match check_duplicate_sites(&files) {
+            Err(CaddyDevError::DuplicateSites(duplicates)) => {
+                assert_eq!(duplicates.len(), 1);
+                assert_eq!(duplicates[0].key, "app.localhost:443");
+                assert_eq!(duplicates[0].sites[0].file, files[0].path);
+                assert_eq!(duplicates[0].sites[1].file, files[1].path);
+            }
+            other => panic!("expected duplicate sites, got {:?}", other),
+        }
+        assert!(check_duplicate_sites(&files[..1]).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn apply_writes_the_main_caddyfile_and_needs_caddy_running() {
+        let root =
+            std::env::temp_dir().join(format!("caddy-dev-reload-apply-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("dev/app")).unwrap();
+        fs::write(
+            root.join("dev/app/Caddyfile.dev"),
+            "app.localhost {\n\treverse_proxy localhost:3000\n}\n",
+        )
+        .unwrap();
+        let main = root.join("Caddyfile");
+        let config = Config {
+            folders: vec![root.join("dev").to_string_lossy().into_owned()],
+            caddy_bin: root.join("no-caddy").to_string_lossy().into_owned(),
+            admin: "127.0.0.1:1".to_string(),
+            ..Config::default()
+        };
+
+        match apply(&config, &main) {
+            Err(CaddyDevError::CaddyNotRunning { address }) => assert_eq!(address, "127.0.0.1:1"),
+            other => panic!("expected Caddy not running, got {:?}", other),
+        }
+        assert_eq!(
+            fs::read_to_string(&main).unwrap(),
+            config.render_caddyfile()
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn error_report_follows_the_import_chain() {
+        let root =
+            std::env::temp_dir().join(format!("caddy-dev-reload-report-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("dev/app")).unwrap();
+        let dev = root.join("dev").to_string_lossy().into_owned();
+        let main = root.join("Caddyfile");
+        fs::write(&main, format!("{{\n}}\n\nimport {}/*/Caddyfile.dev\n", dev)).unwrap();
+        let app = root.join("dev/app/Caddyfile.dev");
+        fs::write(&app, "app.localhost {\n\tbogus\n}\n").unwrap();
+        let config = Config {
+            folders: vec![dev.clone()],
+            ..Config::default()
+        };
+        let message = format!(
+            "Error: adapting config using caddyfile: {}:2: unrecognized directive: bogus, \
+             import chain ['{}:4 (import {}/*/Caddyfile.dev)']\n",
+            app.display(),
+            main.display(),
+            dev
+        );
+
+        let report = caddy_error_report(&config, &main, &message);
+
+        assert_eq!(
+            report,
+            format!(
+                "{message}\n\n\
+                 Caused by {app}:2 (imported from folder '{dev}')\n       2 | \tbogus\n\
+                 Imported by {main}:4 (main Caddyfile)\n       4 | import {dev}/*/Caddyfile.dev",
+                message = message.trim_end(),
+                app = app.display(),
+                main = main.display(),
+                dev = dev,
+            )
+        );
+        assert_eq!(
+            caddy_error_report(&config, &main, "Error: no location\n"),
+            "Error: no location"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
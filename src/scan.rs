@@ -0,0 +1,267 @@
+// src/scan.rs
+//! Discovery of projects with a template or a Caddyfile.dev under a directory tree,
+//! for `caddy-dev scan`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::{Config, normalize_folder};
+use crate::error::CaddyDevError;
+use crate::generate::OUTPUT_FILE_NAME;
+use crate::store;
+use crate::validate::importing_folder;
+
+/// Whether a project's Caddyfile.dev reflects its template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectState {
+    /// Caddyfile.dev is newer than the template
+    UpToDate,
+    /// The template changed after Caddyfile.dev was generated
+    Stale,
+    /// The template was never generated
+    NotGenerated,
+    /// Caddyfile.dev without a template, written by hand
+    NoTemplate,
+}
+
+/// A directory holding a template, a Caddyfile.dev or both
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub dir: PathBuf,
+    pub state: ProjectState,
+    /// Configured folder whose import pattern matches the project's Caddyfile.dev
+    pub imported_by: Option<String>,
+}
+
+/// Find projects under `root`, at most `max_depth` levels down. Files ignored by
+/// `.gitignore` and hidden directories are skipped, except for the generated
+/// Caddyfile.dev next to a template, which is often ignored.
+pub fn scan(root: &Path, max_depth: usize, config: &Config) -> Result<Vec<Project>, CaddyDevError> {
+    if !root.is_dir() {
+        return Err(CaddyDevError::Io {
+            path: root.to_path_buf(),
+            source: io::Error::new(io::ErrorKind::NotFound, "not a directory"),
+        });
+    }
+
+    let template_name = config.generate.template.as_str();
+    // Project directory → whether it holds a template
+    let mut found: BTreeMap<PathBuf, bool> = BTreeMap::new();
+    let walker = ignore::WalkBuilder::new(root)
+        .max_depth(Some(max_depth + 1))
+        .build();
+    for entry in walker.flatten() {
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        let Some(dir) = path.parent() else {
+            continue;
+        };
+        let name = path.file_name().and_then(|name| name.to_str());
+        if name == Some(template_name) {
+            found.insert(dir.to_path_buf(), true);
+        } else if name == Some(OUTPUT_FILE_NAME) {
+            found.entry(dir.to_path_buf()).or_insert(false);
+        }
+    }
+
+    let projects = found
+        .into_iter()
+        .map(|(dir, has_template)| {
+            let output = dir.join(OUTPUT_FILE_NAME);
+            let state = if !has_template {
+                ProjectState::NoTemplate
+            } else {
+                match (modified(&dir.join(template_name)), modified(&output)) {
+                    (_, None) => ProjectState::NotGenerated,
+                    (Some(template), Some(output)) if template > output => ProjectState::Stale,
+                    _ => ProjectState::UpToDate,
+                }
+            };
+            let imported_by = importing_folder(config, &output).map(String::from);
+            Project {
+                dir,
+                state,
+                imported_by,
+            }
+        })
+        .collect();
+    Ok(projects)
+}
+
+/// Folders to configure so that the projects that aren't imported yet are: the parent
+/// folder of each project, or the project's own Caddyfile.dev when it's `root` itself,
+/// whose parent lies outside the scanned tree
+pub fn folders_to_add(root: &Path, projects: &[Project]) -> BTreeSet<String> {
+    projects
+        .iter()
+        .filter(|project| project.imported_by.is_none())
+        .filter_map(|project| {
+            if project.dir == root {
+                Some(project.dir.join(OUTPUT_FILE_NAME))
+            } else {
+                project.dir.parent().map(Path::to_path_buf)
+            }
+        })
+        .map(|folder| normalize_folder(&folder.to_string_lossy()))
+        .collect()
+}
+
+/// Add the folders of the projects that aren't imported yet, see [`folders_to_add`],
+/// and save the configuration. Returns the folders that weren't configured already.
+pub fn add_projects(root: &Path, projects: &[Project]) -> Result<Vec<String>, CaddyDevError> {
+    let folders: Vec<String> = folders_to_add(root, projects).into_iter().collect();
+    let added = store::add_folders(&folders)?;
+    Ok(added
+        .into_iter()
+        .filter_map(|(folder, added)| added.then_some(folder))
+        .collect())
+}
+
+/// Modification time of a file, `None` if it doesn't exist
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Empty directory under the system temp dir, unique to the test
+    fn root_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("caddy-dev-scan-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write a file, creating its directory, with the given modification time
+    fn write(path: &Path, modified: SystemTime) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "app.localhost {\n}\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    fn states(projects: &[Project]) -> Vec<(PathBuf, ProjectState)> {
+        projects.iter().map(|p| (p.dir.clone(), p.state)).collect()
+    }
+
+    #[test]
+    fn finds_projects_in_each_state() {
+        let root = root_dir("states");
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        let now = SystemTime::now();
+        write(&root.join("fresh/Caddyfile.template"), earlier);
+        write(&root.join("fresh/Caddyfile.dev"), now);
+        write(&root.join("stale/Caddyfile.template"), now);
+        write(&root.join("stale/Caddyfile.dev"), earlier);
+        write(&root.join("new/Caddyfile.template"), now);
+        write(&root.join("manual/Caddyfile.dev"), now);
+        let config = Config {
+            folders: vec![root.to_string_lossy().into_owned()],
+            ..Config::default()
+        };
+
+        let projects = scan(&root, 3, &config).unwrap();
+
+        assert_eq!(
+            states(&projects),
+            [
+                (root.join("fresh"), ProjectState::UpToDate),
+                (root.join("manual"), ProjectState::NoTemplate),
+                (root.join("new"), ProjectState::NotGenerated),
+                (root.join("stale"), ProjectState::Stale),
+            ]
+        );
+        let folder = root.to_string_lossy().into_owned();
+        assert_eq!(projects[0].imported_by.as_deref(), Some(folder.as_str()));
+        assert!(folders_to_add(&root, &projects).is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn stops_at_max_depth() {
+        let root = root_dir("depth");
+        let now = SystemTime::now();
+        write(&root.join("a/Caddyfile.dev"), now);
+        write(&root.join("a/b/c/Caddyfile.dev"), now);
+
+        let shallow = scan(&root, 1, &Config::default()).unwrap();
+        assert_eq!(
+            states(&shallow),
+            [(root.join("a"), ProjectState::NoTemplate)]
+        );
+        let deep = scan(&root, 3, &Config::default()).unwrap();
+        assert_eq!(deep.len(), 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn gitignored_output_next_to_a_template_is_still_found() {
+        let root = root_dir("gitignore");
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        let now = SystemTime::now();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "Caddyfile.dev\nnode_modules/\n").unwrap();
+        write(&root.join("app/Caddyfile.template"), earlier);
+        write(&root.join("app/Caddyfile.dev"), now);
+        write(&root.join("app/node_modules/dep/Caddyfile.dev"), now);
+        write(&root.join("manual/Caddyfile.dev"), now);
+
+        let projects = scan(&root, 3, &Config::default()).unwrap();
+
+        assert_eq!(
+            states(&projects),
+            [(root.join("app"), ProjectState::UpToDate)]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn root_project_is_added_by_its_own_file() {
+        let root = root_dir("add");
+        let now = SystemTime::now();
+        write(&root.join("Caddyfile.template"), now);
+        write(&root.join("apps/web/Caddyfile.dev"), now);
+
+        let projects = scan(&root, 3, &Config::default()).unwrap();
+        let folders: Vec<String> = folders_to_add(&root, &projects).into_iter().collect();
+
+        assert_eq!(
+            folders,
+            [
+                root.join("Caddyfile.dev").to_string_lossy().into_owned(),
+                root.join("apps").to_string_lossy().into_owned(),
+            ]
+        );
+        let config = Config {
+            folders: folders.clone(),
+            ..Config::default()
+        };
+        write(&root.join("Caddyfile.dev"), now);
+        let imported: Vec<PathBuf> = config
+            .imported_files()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(
+            imported,
+            [
+                root.join("Caddyfile.dev"),
+                root.join("apps/web/Caddyfile.dev")
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
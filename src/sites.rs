@@ -0,0 +1,473 @@
+// src/sites.rs
+//! Site addresses and reverse_proxy upstreams read from Caddyfile.dev files.
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::config::ImportedFile;
+
+/// A site address served by an imported Caddyfile.dev
+#[derive(Debug, Clone, Serialize)]
+pub struct Site {
+    /// Address as written, e.g. `app.localhost` or `https://app.localhost:8443`
+    pub address: String,
+    /// `reverse_proxy` upstreams of the site block, in order
+    pub upstreams: Vec<String>,
+    /// Caddyfile.dev defining the site
+    pub file: PathBuf,
+    /// Line of the site block
+    pub line: usize,
+}
+
+/// A site block: its addresses and upstreams
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteBlock {
+    pub addresses: Vec<String>,
+    pub upstreams: Vec<String>,
+    pub line: usize,
+}
+
+/// Sites of every imported file, in import order, with the files that could not be read
+pub fn collect_sites(files: &[ImportedFile]) -> (Vec<Site>, Vec<(PathBuf, std::io::Error)>) {
+    let mut sites = Vec::new();
+    let mut unreadable = Vec::new();
+    for file in files {
+        let content = match fs::read_to_string(&file.path) {
+            Ok(content) => content,
+            Err(e) => {
+                unreadable.push((file.path.clone(), e));
+                continue;
+            }
+        };
+        for block in parse_site_blocks(&content) {
+            for address in block.addresses {
+                sites.push(Site {
+                    address,
+                    upstreams: block.upstreams.clone(),
+                    file: file.path.clone(),
+                    line: block.line,
+                });
+            }
+        }
+    }
+    (sites, unreadable)
+}
+
+/// Parse the site blocks of a Caddyfile, as formatted by `caddy fmt`: blocks open with `{`
+/// at the end of a line and close with `}` at the start of one. A block opened and closed
+/// on the same line (`site { respond "x" }`) is read too. Other layouts, like `{` alone on
+/// the line after the addresses, are not recognized and their sites are missed. The
+/// global options block, snippets and named routes are skipped.
+pub fn parse_site_blocks(content: &str) -> Vec<SiteBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<SiteBlock> = None;
+    let mut depth = 0usize;
+    // Depth of the open `reverse_proxy { ... }` block, whose `to` lines list upstreams
+    let mut proxy_depth = None;
+
+    for (line, mut tokens) in tokenize(content) {
+        while tokens.first().is_some_and(|token| token == "}") {
+            tokens.remove(0);
+            depth = depth.saturating_sub(1);
+            if proxy_depth == Some(depth) {
+                proxy_depth = None;
+            }
+            if depth == 0 {
+                blocks.extend(current.take());
+            }
+        }
+
+        // A whole block on one line
+        if depth == 0
+            && tokens.last().is_some_and(|token| token == "}")
+            && let Some(open) = tokens.iter().position(|token| token == "{")
+        {
+            let inner = &tokens[open + 1..tokens.len() - 1];
+            if is_site_label(&tokens[..open]) {
+                let mut site = SiteBlock {
+                    addresses: site_addresses(&tokens[..open]),
+                    upstreams: Vec::new(),
+                    line,
+                };
+                if inner.first().is_some_and(|token| token == "reverse_proxy") {
+                    site.upstreams.extend(proxy_upstreams(&inner[1..]));
+                }
+                blocks.push(site);
+            }
+            continue;
+        }
+
+        let opens = tokens.last().is_some_and(|token| token == "{");
+        if opens {
+            tokens.pop();
+        }
+        let closes = tokens.last().is_some_and(|token| token == "}");
+        if closes {
+            tokens.pop();
+        }
+
+        if depth == 0 {
+            if opens && is_site_label(&tokens) {
+                current = Some(SiteBlock {
+                    addresses: site_addresses(&tokens),
+                    upstreams: Vec::new(),
+                    line,
+                });
+            }
+        } else if let Some(site) = current.as_mut() {
+            match tokens.first().map(String::as_str) {
+                Some("reverse_proxy") => {
+                    site.upstreams.extend(proxy_upstreams(&tokens[1..]));
+                    if opens {
+                        proxy_depth = Some(depth);
+                    }
+                }
+                Some("to") if proxy_depth.is_some() => {
+                    site.upstreams.extend(tokens[1..].iter().cloned());
+                }
+                _ => {}
+            }
+        }
+
+        if opens && !closes {
+            depth += 1;
+        } else if closes && !opens && depth > 0 {
+            depth -= 1;
+            if depth == 0 {
+                blocks.extend(current.take());
+            }
+        }
+    }
+
+    blocks.extend(current);
+    blocks
+}
+
+/// Whether the tokens before a top-level `{` are site addresses, rather than the global
+/// options block (no tokens), a snippet `(name)` or a named route `&(name)`
+fn is_site_label(tokens: &[String]) -> bool {
+    tokens
+        .first()
+        .is_some_and(|token| !token.starts_with('(') && !token.starts_with("&("))
+}
+
+/// Addresses of a site block, separated by commas and/or spaces
+fn site_addresses(tokens: &[String]) -> Vec<String> {
+    tokens
+        .join(" ")
+        .split([',', ' '])
+        .filter(|address| !address.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Upstreams among the arguments of a `reverse_proxy` directive, after its matcher
+fn proxy_upstreams(args: &[String]) -> impl Iterator<Item = String> + '_ {
+    args.iter().skip_while(|token| is_matcher(token)).cloned()
+}
+
+/// Whether a directive argument is a request matcher rather than an upstream
+fn is_matcher(token: &str) -> bool {
+    token == "*" || token.starts_with('@') || token.starts_with('/')
+}
+
+/// Split a Caddyfile into lines of tokens, honoring quotes and dropping comments.
+/// A quoted value spanning lines belongs to the line it starts on.
+fn tokenize(content: &str) -> Vec<(usize, Vec<String>)> {
+    let mut lines: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut token_line = 1;
+    let mut line = 1;
+    let mut chars = content.chars().peekable();
+
+    let mut flush_line = |tokens: &mut Vec<String>, line: usize| {
+        if !tokens.is_empty() {
+            lines.push((line, std::mem::take(tokens)));
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '`' => {
+                if token.is_empty() && tokens.is_empty() {
+                    token_line = line;
+                }
+                let quote = c;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' if quote == '"' && chars.peek() == Some(&'"') => {
+                            token.push('"');
+                            chars.next();
+                        }
+                        c if c == quote => break,
+                        '\n' => {
+                            line += 1;
+                            token.push(c);
+                        }
+                        c => token.push(c),
+                    }
+                }
+                // An empty quoted value is still a token
+                if token.is_empty() {
+                    tokens.push(String::new());
+                }
+            }
+            '#' if token.is_empty() => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                flush_line(&mut tokens, token_line);
+                line += 1;
+            }
+            '\n' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                flush_line(&mut tokens, token_line);
+                line += 1;
+            }
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => {
+                if token.is_empty() && tokens.is_empty() {
+                    token_line = line;
+                }
+                token.push(c);
+            }
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    flush_line(&mut tokens, token_line);
+    lines
+}
+
+/// Sites that listen on the same host and port, which Caddy rejects as ambiguous
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    /// Host and port shared by the sites, see [`site_key`]
+    pub key: String,
+    pub sites: Vec<Site>,
+}
+
+/// Host and port a site address is served on, e.g. `app.localhost:443`. The port
+/// defaults to 80 for `http://` addresses and 443 otherwise.
+pub fn site_key(address: &str) -> String {
+    let address = address.to_lowercase();
+    let (scheme, rest) = match address.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, address.as_str()),
+    };
+    let host_port = rest.split('/').next().unwrap_or(rest);
+    let (host, port) = match host_port.rsplit_once(':') {
+        // Not the inside of an IPv6 address like `[::1]`
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (host_port, None),
+    };
+    let port = port.unwrap_or(if scheme == Some("http") { "80" } else { "443" });
+    format!("{}:{}", host, port)
+}
+
+/// Sites sharing the same host and port, in order of first definition
+pub fn find_duplicates(sites: &[Site]) -> Vec<Duplicate> {
+    let mut groups: Vec<Duplicate> = Vec::new();
+    for site in sites {
+        let key = site_key(&site.address);
+        match groups.iter_mut().find(|group| group.key == key) {
+            Some(group) => group.sites.push(site.clone()),
+            None => groups.push(Duplicate {
+                key,
+                sites: vec![site.clone()],
+            }),
+        }
+    }
+    groups.retain(|group| group.sites.len() > 1);
+    groups
+}
+
+/// Describe duplicate sites, one group per host with the files defining it
+pub fn describe_duplicates(duplicates: &[Duplicate]) -> String {
+    let mut description = String::new();
+    for duplicate in duplicates {
+        description.push_str(&format!(
+            "Site '{}' is defined more than once:\n",
+            duplicate.key
+        ));
+        for site in &duplicate.sites {
+            description.push_str(&format!(
+                "  {}:{} ({})\n",
+                site.file.display(),
+                site.line,
+                site.address
+            ));
+        }
+    }
+    description.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(addresses: &[&str], upstreams: &[&str], line: usize) -> SiteBlock {
+        SiteBlock {
+            addresses: addresses.iter().map(|a| a.to_string()).collect(),
+            upstreams: upstreams.iter().map(|u| u.to_string()).collect(),
+            line,
+        }
+    }
+
+    #[test]
+    fn parses_site_blocks() {
+        let cases = [
+            (
+                "several addresses on one line",
+                "a.localhost, b.localhost {\n\treverse_proxy localhost:3000\n}\n",
+                vec![block(
+                    &["a.localhost", "b.localhost"],
+                    &["localhost:3000"],
+                    1,
+                )],
+            ),
+            (
+                "addresses separated by spaces",
+                "a.localhost b.localhost:8443 {\n}\n",
+                vec![block(&["a.localhost", "b.localhost:8443"], &[], 1)],
+            ),
+            (
+                "matcher arguments are skipped",
+                "app.localhost {\n\treverse_proxy /api/* host:1 host:2\n\treverse_proxy @ws host:3\n\treverse_proxy * host:4\n}\n",
+                vec![block(
+                    &["app.localhost"],
+                    &["host:1", "host:2", "host:3", "host:4"],
+                    1,
+                )],
+            ),
+            (
+                "to lines inside a reverse_proxy block",
+                "app.localhost {\n\treverse_proxy host:1 {\n\t\tto host:2 host:3\n\t\tlb_policy first\n\t}\n\thandle {\n\t\tto ignored:1\n\t}\n}\n",
+                vec![block(
+                    &["app.localhost"],
+                    &["host:1", "host:2", "host:3"],
+                    1,
+                )],
+            ),
+            (
+                "snippets and named routes are skipped",
+                "(common) {\n\treverse_proxy snippet:1\n}\n&(route) {\n\treverse_proxy route:1\n}\napp.localhost {\n\timport common\n}\n",
+                vec![block(&["app.localhost"], &[], 7)],
+            ),
+            (
+                "the global options block is skipped",
+                "{\n\tadmin localhost:2019\n\tservers {\n\t\tprotocols h1\n\t}\n}\n\napp.localhost {\n\treverse_proxy host:1\n}\n",
+                vec![block(&["app.localhost"], &["host:1"], 8)],
+            ),
+            (
+                "quoted tokens and comments",
+                "# app.localhost {\napp.localhost { # the app\n\trespond \"} {\" 200\n\treverse_proxy \"host:1\" # host:2\n}\n",
+                vec![block(&["app.localhost"], &["host:1"], 2)],
+            ),
+            (
+                "a block on one line",
+                "one.localhost { respond \"x\" }\ntwo.localhost { reverse_proxy /api/* host:1 }\n{ debug }\n",
+                vec![
+                    block(&["one.localhost"], &[], 1),
+                    block(&["two.localhost"], &["host:1"], 2),
+                ],
+            ),
+            (
+                "nested blocks and line numbers",
+                "\n\na.localhost {\n\thandle /x {\n\t\treverse_proxy host:1\n\t}\n}\nb.localhost {\n}\n",
+                vec![
+                    block(&["a.localhost"], &["host:1"], 3),
+                    block(&["b.localhost"], &[], 8),
+                ],
+            ),
+        ];
+        for (name, content, expected) in cases {
+            assert_eq!(parse_site_blocks(content), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn tokenize_honors_quotes_and_comments() {
+        let content = "a \"b c\" `d\ne` \"\" # comment\n\n  f#g \"h\\\"i\" #comment\n";
+        let lines = tokenize(content);
+        let expected: Vec<(usize, Vec<String>)> = vec![
+            (1, ["a", "b c", "d\ne", ""].map(String::from).to_vec()),
+            (4, ["f#g", "h\"i"].map(String::from).to_vec()),
+        ];
+        assert_eq!(lines, expected);
+    }
+
+    fn site(address: &str, file: &str, line: usize) -> Site {
+        Site {
+            address: address.to_string(),
+            upstreams: Vec::new(),
+            file: PathBuf::from(file),
+            line,
+        }
+    }
+
+    #[test]
+    fn site_key_defaults_the_port_by_scheme() {
+        let cases = [
+            ("app.localhost", "app.localhost:443"),
+            ("https://app.localhost", "app.localhost:443"),
+            ("http://app.localhost", "app.localhost:80"),
+            ("http://app.localhost:8080/path", "app.localhost:8080"),
+            ("app.localhost:8080", "app.localhost:8080"),
+            (":8080", ":8080"),
+            ("[::1]:443", "[::1]:443"),
+            ("[::1]", "[::1]:443"),
+            ("http://[::1]", "[::1]:80"),
+            ("App.LocalHost", "app.localhost:443"),
+            ("HTTP://APP.localhost", "app.localhost:80"),
+        ];
+        for (address, key) in cases {
+            assert_eq!(site_key(address), key, "{}", address);
+        }
+    }
+
+    #[test]
+    fn find_duplicates_groups_sites_on_the_same_host_and_port() {
+        let sites = [
+            site("app.localhost", "/a/Caddyfile.dev", 1),
+            site("http://app.localhost", "/a/Caddyfile.dev", 5),
+            site("api.localhost", "/b/Caddyfile.dev", 1),
+            site("https://APP.localhost:443", "/b/Caddyfile.dev", 4),
+        ];
+
+        let duplicates = find_duplicates(&sites);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].key, "app.localhost:443");
+        let lines: Vec<usize> = duplicates[0].sites.iter().map(|s| s.line).collect();
+        assert_eq!(lines, [1, 4]);
+        assert_eq!(
+            describe_duplicates(&duplicates),
+            "Site 'app.localhost:443' is defined more than once:\n  \
+             /a/Caddyfile.dev:1 (app.localhost)\n  \
+             /b/Caddyfile.dev:4 (https://APP.localhost:443)"
+        );
+    }
+
+    #[test]
+    fn find_duplicates_ignores_distinct_ports() {
+        let sites = [
+            site("app.localhost", "/a/Caddyfile.dev", 1),
+            site("app.localhost:8443", "/a/Caddyfile.dev", 4),
+            site("http://app.localhost", "/a/Caddyfile.dev", 7),
+        ];
+        assert!(find_duplicates(&sites).is_empty());
+    }
+}
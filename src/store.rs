@@ -0,0 +1,154 @@
+// src/store.rs
+//! Files caddy-dev keeps in its config directory: `config.toml`, the main Caddyfile
+//! derived from it and the port registry.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{self, Config};
+use crate::error::CaddyDevError;
+use crate::ports::{self, PortRegistry};
+
+/// Get the caddy-dev config directory (~/.config/caddy-dev)
+pub fn get_config_dir() -> PathBuf {
+    // Use XDG-compliant ~/.config/caddy-dev for cross-platform consistency
+    // This overrides the platform-specific config_dir() to ensure consistent behavior
+    if let Some(home_dir) = dirs::home_dir() {
+        home_dir.join(".config").join("caddy-dev")
+    } else {
+        // Fallback to platform-specific config directory if home not found
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("/home/.config"))
+            .join("caddy-dev")
+    }
+}
+
+/// Get the main Caddyfile path in config directory
+pub fn get_main_caddyfile_path() -> PathBuf {
+    get_config_dir().join("Caddyfile")
+}
+
+/// Get the configuration file path in config directory
+pub fn get_config_path() -> PathBuf {
+    get_config_dir().join(config::CONFIG_FILE_NAME)
+}
+
+/// Load the configuration, falling back to the folders recorded in a main Caddyfile
+/// generated before config.toml existed
+pub fn load_config() -> Result<Config, CaddyDevError> {
+    match Config::load(&get_config_path())? {
+        Some(config) => Ok(config),
+        None => Ok(fs::read_to_string(get_main_caddyfile_path())
+            .map(|content| Config::from_legacy_caddyfile(&content))
+            .unwrap_or_default()),
+    }
+}
+
+/// Create the config directory if needed
+pub fn create_config_dir() -> Result<PathBuf, CaddyDevError> {
+    let config_dir = get_config_dir();
+    fs::create_dir_all(&config_dir).map_err(|source| CaddyDevError::Io {
+        path: config_dir.clone(),
+        source,
+    })?;
+    Ok(config_dir)
+}
+
+/// Save the configuration and regenerate the main Caddyfile from it
+pub fn save_config(config: &Config) -> Result<(), CaddyDevError> {
+    create_config_dir()?;
+    config.save(&get_config_path())?;
+    write_main_caddyfile(config)
+}
+
+/// Regenerate the main Caddyfile from the configuration
+pub fn write_main_caddyfile(config: &Config) -> Result<(), CaddyDevError> {
+    let main_caddyfile_path = get_main_caddyfile_path();
+    fs::write(&main_caddyfile_path, config.render_caddyfile()).map_err(|source| CaddyDevError::Io {
+        path: main_caddyfile_path,
+        source,
+    })
+}
+
+/// Regenerate the main Caddyfile from config.toml and return its path.
+/// Fails with [`CaddyDevError::ConfigMissing`] if caddy-dev hasn't been initialized.
+pub fn prepare_main_caddyfile(config: &Config) -> Result<PathBuf, CaddyDevError> {
+    let main_caddyfile_path = get_main_caddyfile_path();
+
+    // The main Caddyfile is always derived from config.toml, which may have been edited,
+    // and ** patterns are expanded again to pick up new projects
+    if get_config_path().exists() || config.has_recursive_folders() {
+        write_main_caddyfile(config)?;
+    }
+
+    if !main_caddyfile_path.exists() {
+        return Err(CaddyDevError::ConfigMissing {
+            path: main_caddyfile_path,
+        });
+    }
+    Ok(main_caddyfile_path)
+}
+
+/// Existing config.toml, or main Caddyfile from an older version, that `init` would replace
+pub fn existing_config() -> Option<PathBuf> {
+    [get_config_path(), get_main_caddyfile_path()]
+        .into_iter()
+        .find(|path| path.exists())
+}
+
+/// Configure exactly these folders, normalized, keeping the other settings, and write
+/// config.toml and the main Caddyfile
+pub fn init_folders(folders: &[String]) -> Result<Config, CaddyDevError> {
+    let mut config = load_config()?;
+    config.folders.clear();
+    for folder in folders {
+        config.add_folder(config::normalize_folder(folder));
+    }
+    save_config(&config)?;
+    Ok(config)
+}
+
+/// Add folders, normalized, and save the configuration. Returns each folder with
+/// whether it was added, rather than already configured.
+pub fn add_folders(folders: &[String]) -> Result<Vec<(String, bool)>, CaddyDevError> {
+    let mut config = load_config()?;
+    let added = folders
+        .iter()
+        .map(|folder| {
+            let folder = config::normalize_folder(folder);
+            let added = config.add_folder(folder.clone());
+            (folder, added)
+        })
+        .collect();
+    save_config(&config)?;
+    Ok(added)
+}
+
+/// Folders removed by [`remove_folders`]
+#[derive(Debug, Clone, Default)]
+pub struct RemovedFolders {
+    pub removed: Vec<String>,
+    /// Folders that weren't configured, as given
+    pub missing: Vec<String>,
+}
+
+/// Remove folders, given as listed or as they were entered when added, and save the
+/// configuration
+pub fn remove_folders(folders: &[String]) -> Result<RemovedFolders, CaddyDevError> {
+    let mut config = load_config()?;
+    let mut result = RemovedFolders::default();
+    for folder in folders {
+        if config.remove_folder(folder) || config.remove_folder(&config::normalize_folder(folder)) {
+            result.removed.push(folder.clone());
+        } else {
+            result.missing.push(folder.clone());
+        }
+    }
+    save_config(&config)?;
+    Ok(result)
+}
+
+/// Load the port registry from the config directory
+pub fn load_port_registry() -> Result<PortRegistry, CaddyDevError> {
+    PortRegistry::load(&get_config_dir().join(ports::REGISTRY_FILE_NAME))
+}
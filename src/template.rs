@@ -0,0 +1,855 @@
+// src/template.rs
+//! Template engine for Caddyfile.template files.
+//!
+//! Supported syntax:
+//!
+//! - `{{key}}` — variable substitution
+//! - `{{key | default: 3000}}` — filters, chained with `|` (`default`, `lower`, `upper`, `slug`, `trim`)
+//! - `{{#if key}}...{{else}}...{{/if}}` — conditional blocks
+//! - `{{#each list}}...{{this}}...{{/each}}` — loops over comma-separated list variables
+//! - `\{{` — a literal `{{`, to keep Caddy's own template syntax (`\{{ .Host }}`)
+//!
+//! Variable names can't start with `.`, so a Caddy template action left unescaped is
+//! reported instead of being taken for a missing variable.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// Position of a tag inside the template source (1-based)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Error raised while parsing a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl TemplateError {
+    fn new(message: impl Into<String>, position: Position) -> Self {
+        Self {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at {})", self.message, self.position)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// A filter applied to a value with `|`
+#[derive(Debug, Clone)]
+enum Filter {
+    Default(String),
+    Lower,
+    Upper,
+    Slug,
+    Trim,
+}
+
+impl Filter {
+    fn apply(&self, value: Option<String>) -> Option<String> {
+        match self {
+            Filter::Default(fallback) => match value {
+                Some(v) if !v.is_empty() => Some(v),
+                _ => Some(fallback.clone()),
+            },
+            Filter::Lower => value.map(|v| v.to_lowercase()),
+            Filter::Upper => value.map(|v| v.to_uppercase()),
+            Filter::Slug => value.map(|v| slugify(&v)),
+            Filter::Trim => value.map(|v| v.trim().to_string()),
+        }
+    }
+}
+
+/// A variable reference with its filters, e.g. `port | default: 3000`
+#[derive(Debug, Clone)]
+struct Expr {
+    name: String,
+    filters: Vec<Filter>,
+    /// Original tag text, written back verbatim when the variable is unresolved
+    source: String,
+    position: Position,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Expr(Expr),
+    If {
+        cond: Expr,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Each {
+        list: Expr,
+        binding: Option<String>,
+        body: Vec<Node>,
+    },
+}
+
+/// A placeholder that had no value and no `default` filter when rendering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unresolved {
+    pub name: String,
+    pub position: Position,
+}
+
+/// Result of rendering a template
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub output: String,
+    /// Unresolved placeholders in source order, left verbatim in `output`
+    pub unresolved: Vec<Unresolved>,
+}
+
+/// A parsed template, ready to be rendered against a set of variables
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    /// Parse template source into a renderable template
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter(),
+        };
+        let (nodes, end) = parser.parse_nodes()?;
+        if let Some((tag, position)) = end {
+            return Err(TemplateError::new(
+                format!("Unexpected '{{{{{}}}}}' without a matching block", tag),
+                position,
+            ));
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Render the template. Unresolved placeholders are left in the output unchanged
+    /// and reported in [`Rendered::unresolved`].
+    pub fn render(&self, vars: &HashMap<String, String>) -> Rendered {
+        let mut rendered = Rendered {
+            output: String::new(),
+            unresolved: Vec::new(),
+        };
+        let mut scopes: Vec<HashMap<String, String>> = Vec::new();
+        render_nodes(&self.nodes, vars, &mut scopes, &mut rendered);
+        rendered
+            .unresolved
+            .sort_by_key(|u| (u.position.line, u.position.column));
+        rendered.unresolved.dedup();
+        rendered
+    }
+
+    /// Names of all variables the template references, in any branch.
+    /// Loop variables (`this`, `@index`, `as` bindings) are not included.
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        collect_variables(&self.nodes, &mut Vec::new(), &mut names);
+        names
+    }
+}
+
+fn collect_variables(nodes: &[Node], locals: &mut Vec<String>, names: &mut BTreeSet<String>) {
+    let add = |expr: &Expr, locals: &[String], names: &mut BTreeSet<String>| {
+        let is_local =
+            expr.name == "this" || expr.name.starts_with('@') || locals.contains(&expr.name);
+        if !is_local {
+            names.insert(expr.name.clone());
+        }
+    };
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Expr(expr) => add(expr, locals, names),
+            Node::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                add(cond, locals, names);
+                collect_variables(then, locals, names);
+                collect_variables(otherwise, locals, names);
+            }
+            Node::Each {
+                list,
+                binding,
+                body,
+            } => {
+                add(list, locals, names);
+                locals.extend(binding.clone());
+                collect_variables(body, locals, names);
+                if binding.is_some() {
+                    locals.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Convert a value into a DNS-friendly slug (`Feature/Login_Page` → `feature-login-page`)
+pub fn slugify(value: &str) -> String {
+    let mut slug = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Whether a rendered value counts as true in `{{#if}}`
+fn is_truthy(value: &str) -> bool {
+    !matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "" | "false" | "0" | "no" | "off"
+    )
+}
+
+/// Split a list variable (`web, api,worker`) into its items
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn lookup(
+    name: &str,
+    vars: &HashMap<String, String>,
+    scopes: &[HashMap<String, String>],
+) -> Option<String> {
+    scopes
+        .iter()
+        .rev()
+        .find_map(|scope| scope.get(name))
+        .or_else(|| vars.get(name))
+        .cloned()
+}
+
+fn evaluate(
+    expr: &Expr,
+    vars: &HashMap<String, String>,
+    scopes: &[HashMap<String, String>],
+) -> Option<String> {
+    expr.filters
+        .iter()
+        .fold(lookup(&expr.name, vars, scopes), |value, filter| {
+            filter.apply(value)
+        })
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    vars: &HashMap<String, String>,
+    scopes: &mut Vec<HashMap<String, String>>,
+    rendered: &mut Rendered,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => rendered.output.push_str(text),
+            Node::Expr(expr) => match evaluate(expr, vars, scopes) {
+                Some(value) => rendered.output.push_str(&value),
+                None => {
+                    rendered.output.push_str(&expr.source);
+                    rendered.unresolved.push(Unresolved {
+                        name: expr.name.clone(),
+                        position: expr.position,
+                    });
+                }
+            },
+            Node::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let truthy = evaluate(cond, vars, scopes).is_some_and(|v| is_truthy(&v));
+                let branch = if truthy { then } else { otherwise };
+                render_nodes(branch, vars, scopes, rendered);
+            }
+            Node::Each {
+                list,
+                binding,
+                body,
+            } => {
+                let items = evaluate(list, vars, scopes)
+                    .map(|v| split_list(&v))
+                    .unwrap_or_default();
+                let count = items.len();
+                for (index, item) in items.into_iter().enumerate() {
+                    let mut scope = HashMap::new();
+                    scope.insert("@index".to_string(), index.to_string());
+                    scope.insert("@first".to_string(), (index == 0).to_string());
+                    scope.insert("@last".to_string(), (index + 1 == count).to_string());
+                    if let Some(binding) = binding {
+                        scope.insert(binding.clone(), item.clone());
+                    }
+                    scope.insert("this".to_string(), item);
+                    scopes.push(scope);
+                    render_nodes(body, vars, scopes, rendered);
+                    scopes.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Raw token produced by the tokenizer
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    /// Trimmed tag content and the original tag text including braces
+    Tag {
+        content: String,
+        source: String,
+        position: Position,
+    },
+}
+
+/// Whether a tag is a block tag (`#if`, `else`, `/each`, ...) rather than a value
+fn is_block_tag(content: &str) -> bool {
+    content.starts_with('#') || content.starts_with('/') || content == "else"
+}
+
+/// Compute the line/column of a byte offset in the source
+fn position_at(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    Position { line, column }
+}
+
+/// Split the source into text and `{{...}}` tags. `\{{` is kept as a literal `{{`,
+/// and the rest of it up to `}}` as text.
+///
+/// Block tags that sit alone on a line swallow that line, so `{{#if}}` and
+/// `{{/if}}` don't leave blank lines behind in the generated Caddyfile.
+fn tokenize(source: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = 0;
+
+    while let Some(start) = source[rest..].find("{{").map(|i| rest + i) {
+        text.push_str(&source[rest..start]);
+        if source[..start].ends_with('\\') {
+            text.pop();
+            text.push_str("{{");
+            rest = start + 2;
+            continue;
+        }
+        let position = position_at(source, start);
+        let end = match source[start + 2..].find("}}") {
+            Some(i) => start + 2 + i,
+            None => return Err(TemplateError::new("Unclosed tag '{{'", position)),
+        };
+        let content = source[start + 2..end].trim().to_string();
+        let tag_source = source[start..end + 2].to_string();
+        rest = end + 2;
+
+        if is_block_tag(&content) {
+            let line_prefix = text.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let line_suffix = source[rest..].find('\n');
+            let until_eol = match line_suffix {
+                Some(i) => &source[rest..rest + i],
+                None => &source[rest..],
+            };
+            let at_line_end = until_eol
+                .chars()
+                .all(|c| c == ' ' || c == '\t' || c == '\r');
+            if at_line_end && starts_line(source, start) {
+                text.truncate(line_prefix);
+                rest = match line_suffix {
+                    Some(i) => rest + i + 1,
+                    None => source.len(),
+                };
+            }
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(std::mem::take(&mut text)));
+        }
+        tokens.push(Token::Tag {
+            content,
+            source: tag_source,
+            position,
+        });
+    }
+
+    text.push_str(&source[rest..]);
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok(tokens)
+}
+
+/// Whether only spaces/tabs precede `offset` on its line in the original source
+fn starts_line(source: &str, offset: usize) -> bool {
+    let line_start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    source[line_start..offset]
+        .chars()
+        .all(|c| c == ' ' || c == '\t')
+}
+
+/// The closing or `else` tag that ended a run of nodes, if any
+type BlockEnd = Option<(String, Position)>;
+
+struct Parser {
+    tokens: std::vec::IntoIter<Token>,
+}
+
+impl Parser {
+    /// Parse nodes until a closing or `else` tag, which is returned to the caller
+    fn parse_nodes(&mut self) -> Result<(Vec<Node>, BlockEnd), TemplateError> {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.tokens.next() {
+            match token {
+                Token::Text(text) => nodes.push(Node::Text(text)),
+                Token::Tag {
+                    content,
+                    source,
+                    position,
+                } => {
+                    if content == "else" || content.starts_with('/') {
+                        return Ok((nodes, Some((content, position))));
+                    }
+                    let (keyword, rest) = content
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((&content, ""));
+                    match keyword {
+                        "#if" => nodes.push(self.parse_if(rest, source, position)?),
+                        "#each" => nodes.push(self.parse_each(rest, source, position)?),
+                        _ if keyword.starts_with('#') => {
+                            return Err(TemplateError::new(
+                                format!("Unknown block '{}'", keyword),
+                                position,
+                            ));
+                        }
+                        _ => nodes.push(Node::Expr(parse_expr(&content, source, position)?)),
+                    }
+                }
+            }
+        }
+        Ok((nodes, None))
+    }
+
+    fn parse_if(
+        &mut self,
+        rest: &str,
+        source: String,
+        position: Position,
+    ) -> Result<Node, TemplateError> {
+        let cond = parse_expr(rest.trim(), source, position)?;
+        let (then, end) = self.parse_nodes()?;
+        let (otherwise, end) = match end {
+            Some((tag, _)) if tag == "else" => self.parse_nodes()?,
+            other => (Vec::new(), other),
+        };
+        expect_close(end, "if", position)?;
+        Ok(Node::If {
+            cond,
+            then,
+            otherwise,
+        })
+    }
+
+    fn parse_each(
+        &mut self,
+        rest: &str,
+        source: String,
+        position: Position,
+    ) -> Result<Node, TemplateError> {
+        let (list, binding) = match rest.split_once(" as ") {
+            Some((list, binding)) => {
+                let binding = binding.trim();
+                if !is_identifier(binding) {
+                    return Err(TemplateError::new(
+                        format!("Invalid loop variable name '{}'", binding),
+                        position,
+                    ));
+                }
+                (list, Some(binding.to_string()))
+            }
+            None => (rest, None),
+        };
+        let list = parse_expr(list.trim(), source, position)?;
+        let (body, end) = self.parse_nodes()?;
+        if matches!(&end, Some((tag, _)) if tag == "else") {
+            return Err(TemplateError::new(
+                "'{{else}}' is not supported inside '{{#each}}'",
+                end.map(|(_, p)| p).unwrap_or(position),
+            ));
+        }
+        expect_close(end, "each", position)?;
+        Ok(Node::Each {
+            list,
+            binding,
+            body,
+        })
+    }
+}
+
+fn expect_close(end: BlockEnd, block: &str, opened_at: Position) -> Result<(), TemplateError> {
+    match end {
+        Some((tag, _)) if tag[1..].trim() == block => Ok(()),
+        Some((tag, position)) => Err(TemplateError::new(
+            format!("Expected '{{{{/{}}}}}' but found '{{{{{}}}}}'", block, tag),
+            position,
+        )),
+        None => Err(TemplateError::new(
+            format!("Unclosed '{{{{#{}}}}}' block", block),
+            opened_at,
+        )),
+    }
+}
+
+/// Whether `name` can be used as a variable name in a placeholder
+pub fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '@' | ':'))
+}
+
+/// Parse `name | filter | filter: arg`
+fn parse_expr(content: &str, source: String, position: Position) -> Result<Expr, TemplateError> {
+    let mut parts = split_pipes(content).into_iter();
+    let name = parts.next().unwrap_or_default().trim().to_string();
+    if name.starts_with('.') {
+        return Err(TemplateError::new(
+            format!(
+                "Invalid variable name '{}'; write '\\{{{{' to keep Caddy's template syntax",
+                name
+            ),
+            position,
+        ));
+    }
+    if !is_identifier(&name) {
+        return Err(TemplateError::new(
+            format!("Invalid variable name '{}'", name),
+            position,
+        ));
+    }
+
+    let filters = parts
+        .map(|part| parse_filter(part.trim(), position))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Expr {
+        name,
+        filters,
+        source,
+        position,
+    })
+}
+
+/// Split on `|`, ignoring pipes inside quoted filter arguments
+fn split_pipes(content: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut quote: Option<char> = None;
+    for c in content.chars() {
+        match (quote, c) {
+            (None, '|') => parts.push(String::new()),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                parts.last_mut().unwrap().push(c);
+            }
+            (Some(q), _) if q == c => {
+                quote = None;
+                parts.last_mut().unwrap().push(c);
+            }
+            _ => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts
+}
+
+fn parse_filter(part: &str, position: Position) -> Result<Filter, TemplateError> {
+    let (name, arg) = match part.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(unquote(arg.trim()))),
+        None => (part, None),
+    };
+
+    let filter = match (name, arg) {
+        ("default", Some(arg)) => Filter::Default(arg),
+        ("default", None) => {
+            return Err(TemplateError::new(
+                "Filter 'default' requires a value, e.g. 'default: 3000'",
+                position,
+            ));
+        }
+        ("lower", None) => Filter::Lower,
+        ("upper", None) => Filter::Upper,
+        ("slug", None) => Filter::Slug,
+        ("trim", None) => Filter::Trim,
+        ("lower" | "upper" | "slug" | "trim", Some(_)) => {
+            return Err(TemplateError::new(
+                format!("Filter '{}' does not take an argument", name),
+                position,
+            ));
+        }
+        _ => {
+            return Err(TemplateError::new(
+                format!("Unknown filter '{}'", name),
+                position,
+            ));
+        }
+    };
+    Ok(filter)
+}
+
+/// Strip matching surrounding quotes from a filter argument
+fn unquote(arg: &str) -> String {
+    for q in ['"', '\''] {
+        if arg.len() >= 2 && arg.starts_with(q) && arg.ends_with(q) {
+            return arg[1..arg.len() - 1].to_string();
+        }
+    }
+    arg.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, vars: &[(&str, &str)]) -> Rendered {
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Template::parse(source).unwrap().render(&vars)
+    }
+
+    fn output(source: &str, vars: &[(&str, &str)]) -> String {
+        render(source, vars).output
+    }
+
+    fn parse_error(source: &str) -> TemplateError {
+        Template::parse(source).unwrap_err()
+    }
+
+    fn at(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        assert_eq!(output("{{ a }}:{{b}}", &[("a", "x"), ("b", "1")]), "x:1");
+    }
+
+    #[test]
+    fn applies_filters() {
+        assert_eq!(output("{{v | upper}}", &[("v", "Ab")]), "AB");
+        assert_eq!(output("{{v | lower}}", &[("v", "Ab")]), "ab");
+        assert_eq!(output("{{v | trim}}", &[("v", "  a ")]), "a");
+        assert_eq!(
+            output("{{v | slug}}", &[("v", "Feature/Login_Page")]),
+            "feature-login-page"
+        );
+    }
+
+    #[test]
+    fn chains_filters_left_to_right() {
+        assert_eq!(
+            output("{{v | trim | slug | upper}}", &[("v", " My App ")]),
+            "MY-APP"
+        );
+        assert_eq!(
+            output("{{v | default: Some Name | slug}}", &[]),
+            "some-name"
+        );
+    }
+
+    #[test]
+    fn default_filter_applies_to_missing_and_empty_values() {
+        assert_eq!(output("{{port | default: 3000}}", &[]), "3000");
+        assert_eq!(output("{{port | default: 3000}}", &[("port", "")]), "3000");
+        assert_eq!(
+            output("{{port | default: 3000}}", &[("port", "8080")]),
+            "8080"
+        );
+    }
+
+    #[test]
+    fn quoted_default_may_contain_pipes() {
+        assert_eq!(output("{{v | default:\"x|y\"}}", &[]), "x|y");
+        assert_eq!(output("{{v | default: 'a | b' | upper}}", &[]), "A | B");
+    }
+
+    #[test]
+    fn reports_unresolved_placeholders_verbatim() {
+        let rendered = render("a\n  {{ missing | upper }} {{ok}}", &[("ok", "1")]);
+        assert_eq!(rendered.output, "a\n  {{ missing | upper }} 1");
+        assert_eq!(
+            rendered.unresolved,
+            vec![Unresolved {
+                name: "missing".to_string(),
+                position: at(2, 3),
+            }]
+        );
+    }
+
+    #[test]
+    fn if_else_picks_a_branch() {
+        let source = "{{#if tls}}on{{else}}off{{/if}}";
+        assert_eq!(output(source, &[("tls", "yes")]), "on");
+        assert_eq!(output(source, &[]), "off");
+        for falsy in ["", "false", "0", "no", "OFF"] {
+            assert_eq!(output(source, &[("tls", falsy)]), "off", "{:?}", falsy);
+        }
+    }
+
+    #[test]
+    fn missing_if_condition_is_not_unresolved() {
+        assert!(render("{{#if tls}}on{{/if}}", &[]).unresolved.is_empty());
+    }
+
+    #[test]
+    fn each_exposes_item_index_first_and_last() {
+        let source = "{{#each services as s}}{{@index}}={{s}}/{{this}}\
+                      {{#if @first}}F{{/if}}{{#if @last}}L{{/if}};{{/each}}";
+        assert_eq!(
+            output(source, &[("services", "api, ws ,,web")]),
+            "0=api/apiF;1=ws/ws;2=web/webL;"
+        );
+        assert_eq!(output(source, &[]), "");
+    }
+
+    #[test]
+    fn block_tags_alone_on_a_line_remove_it() {
+        let source = "a {\n    {{#if tls}}\n    tls internal\n    {{/if}}\n}\n";
+        assert_eq!(
+            output(source, &[("tls", "1")]),
+            "a {\n    tls internal\n}\n"
+        );
+        assert_eq!(output(source, &[]), "a {\n}\n");
+    }
+
+    #[test]
+    fn inline_block_tags_keep_their_line() {
+        assert_eq!(output("x {{#if a}}y{{/if}}\nz", &[("a", "1")]), "x y\nz");
+        assert_eq!(
+            output("{{#each l}}\n- {{this}}\n{{/each}}\n", &[("l", "a,b")]),
+            "- a\n- b\n"
+        );
+    }
+
+    #[test]
+    fn unclosed_block_points_to_the_opening_tag() {
+        let error = parse_error("a\n  {{#if x}}\nb\n");
+        assert_eq!(error.message, "Unclosed '{{#if}}' block");
+        assert_eq!(error.position, at(2, 3));
+    }
+
+    #[test]
+    fn mismatched_close_points_to_the_closing_tag() {
+        let error = parse_error("{{#each l}}\n  x {{/if}}");
+        assert_eq!(error.message, "Expected '{{/each}}' but found '{{/if}}'");
+        assert_eq!(error.position, at(2, 5));
+    }
+
+    #[test]
+    fn stray_close_is_reported() {
+        let error = parse_error("a {{/if}}");
+        assert_eq!(
+            error.message,
+            "Unexpected '{{/if}}' without a matching block"
+        );
+        assert_eq!(error.position, at(1, 3));
+    }
+
+    #[test]
+    fn unknown_block_is_reported() {
+        let error = parse_error("x\n{{#unless a}}{{/unless}}");
+        assert_eq!(error.message, "Unknown block '#unless'");
+        assert_eq!(error.position, at(2, 1));
+    }
+
+    #[test]
+    fn block_keywords_must_match_exactly() {
+        let error = parse_error("{{#iffy}}x{{/if}}");
+        assert_eq!(error.message, "Unknown block '#iffy'");
+        assert_eq!(
+            parse_error("{{#eachx}}{{/each}}").message,
+            "Unknown block '#eachx'"
+        );
+        assert_eq!(output("{{#if\ta}}y{{/if}}", &[("a", "1")]), "y");
+    }
+
+    #[test]
+    fn escaped_tags_are_kept_verbatim() {
+        let source = "respond \"\\{{ .Host }} \\{{.Req.Header \"X\"}} {{name}}\"";
+        assert_eq!(
+            output(source, &[("name", "app")]),
+            "respond \"{{ .Host }} {{.Req.Header \"X\"}} app\""
+        );
+        let template = Template::parse("\\{{#if x}}{{y}}").unwrap();
+        assert_eq!(template.variables().into_iter().collect::<Vec<_>>(), ["y"]);
+    }
+
+    #[test]
+    fn caddy_template_actions_must_be_escaped() {
+        let error = parse_error("a\n  {{ .Host }}");
+        assert_eq!(
+            error.message,
+            "Invalid variable name '.Host'; write '\\{{' to keep Caddy's template syntax"
+        );
+        assert_eq!(error.position, at(2, 3));
+        assert!(
+            parse_error("{{ .Req.Header \"X\" }}")
+                .message
+                .starts_with("Invalid variable name '.Req.Header \"X\"'; ")
+        );
+        assert!(!is_identifier(".Host"));
+        assert!(is_identifier("git.branch"));
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        assert_eq!(parse_error("a {{b").message, "Unclosed tag '{{'");
+        assert_eq!(parse_error("{{b | nope}}").message, "Unknown filter 'nope'");
+        assert_eq!(
+            parse_error("{{b | upper: x}}").message,
+            "Filter 'upper' does not take an argument"
+        );
+        assert_eq!(
+            parse_error("{{a b}}").message,
+            "Invalid variable name 'a b'"
+        );
+        assert_eq!(
+            parse_error("{{#each l}}a{{else}}b{{/each}}").message,
+            "'{{else}}' is not supported inside '{{#each}}'"
+        );
+    }
+
+    #[test]
+    fn variables_skip_loop_locals() {
+        let template = Template::parse(
+            "{{host}}{{#if tls}}{{#each services as s}}{{s}}{{this}}{{@index}}{{port}}{{/each}}{{/if}}{{s}}",
+        )
+        .unwrap();
+        let names: Vec<String> = template.variables().into_iter().collect();
+        assert_eq!(names, ["host", "port", "s", "services", "tls"]);
+    }
+}
@@ -0,0 +1,342 @@
+// src/validate.rs
+//! Validation of the main Caddyfile and mapping of Caddy errors back to imported files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::admin::{AdminClient, AdminError};
+use crate::config::{Config, ImportedFile, import_pattern, match_options};
+
+/// Result of running `caddy validate`
+#[derive(Debug)]
+pub enum Validation {
+    Valid,
+    /// Caddy's error message
+    Invalid(String),
+    /// The caddy binary could not be executed
+    Unavailable(std::io::Error),
+}
+
+/// Run `caddy validate` on a Caddyfile
+pub fn run_caddy_validate(caddy_bin: &str, caddyfile: &Path) -> Validation {
+    let output = Command::new(caddy_bin)
+        .arg("validate")
+        .arg("--config")
+        .arg(caddyfile)
+        .args(["--adapter", "caddyfile"])
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => Validation::Valid,
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Validation::Invalid(error_message(&format!("{}{}", stdout, stderr)))
+        }
+        Err(e) => Validation::Unavailable(e),
+    }
+}
+
+/// Validate a Caddyfile with `caddy validate`, or through the admin API's adapter
+/// (syntax only) when the caddy binary is not available
+pub fn check_caddyfile(config: &Config, caddyfile: &Path) -> Validation {
+    match run_caddy_validate(&config.caddy_bin, caddyfile) {
+        Validation::Unavailable(e) => {
+            let content = match fs::read_to_string(caddyfile) {
+                Ok(content) => content,
+                Err(e) => return Validation::Unavailable(e),
+            };
+            match AdminClient::new(&config.admin).adapt_caddyfile(&content) {
+                Ok(_) => Validation::Valid,
+                Err(AdminError::Api { body, .. }) => Validation::Invalid(body),
+                Err(_) => Validation::Unavailable(e),
+            }
+        }
+        validation => validation,
+    }
+}
+
+/// An imported file that failed validation on its own
+#[derive(Debug)]
+pub struct BrokenFile {
+    pub file: ImportedFile,
+    pub message: String,
+}
+
+/// Validate each imported file on its own, with the global options of the main Caddyfile.
+/// Returns the valid files and the broken ones.
+pub fn check_each_file(
+    config: &Config,
+    files: Vec<ImportedFile>,
+) -> Result<(Vec<ImportedFile>, Vec<BrokenFile>), std::io::Error> {
+    let scratch =
+        std::env::temp_dir().join(format!("caddy-dev-check-{}.Caddyfile", std::process::id()));
+    let mut valid = Vec::new();
+    let mut broken = Vec::new();
+
+    for file in files {
+        fs::write(
+            &scratch,
+            config.render_caddyfile_with_files(std::slice::from_ref(&file), &[]),
+        )?;
+        match check_caddyfile(config, &scratch) {
+            Validation::Valid => valid.push(file),
+            Validation::Invalid(message) => broken.push(BrokenFile { file, message }),
+            Validation::Unavailable(e) => {
+                let _ = fs::remove_file(&scratch);
+                return Err(e);
+            }
+        }
+    }
+
+    let _ = fs::remove_file(&scratch);
+    Ok((valid, broken))
+}
+
+/// Extract the error from Caddy's output, skipping its JSON log lines
+fn error_message(output: &str) -> String {
+    let errors: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("Error:"))
+        .collect();
+    if errors.is_empty() {
+        output.trim().to_string()
+    } else {
+        errors.join("\n")
+    }
+}
+
+/// A file and line referenced by a Caddy error message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorLocation {
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+/// Find `path:line` references to existing files in a Caddy error message.
+/// The innermost file comes first: Caddy reports it before the import chain.
+pub fn locate(message: &str) -> Vec<ErrorLocation> {
+    let mut locations: Vec<ErrorLocation> = Vec::new();
+    let separators =
+        |c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '[' | ']' | '(' | ')' | ',');
+    for token in message.split(separators) {
+        let Some((file, line)) = token.trim_end_matches(':').rsplit_once(':') else {
+            continue;
+        };
+        let Ok(line) = line.parse::<usize>() else {
+            continue;
+        };
+        let file = PathBuf::from(file);
+        if !file.is_file() {
+            continue;
+        }
+        let location = ErrorLocation { file, line };
+        if !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+    locations
+}
+
+/// Configured folder whose import pattern matches `file`
+pub fn importing_folder<'a>(config: &'a Config, file: &Path) -> Option<&'a str> {
+    config
+        .folders
+        .iter()
+        .find(|folder| {
+            let pattern = import_pattern(folder);
+            glob::Pattern::new(&pattern)
+                .is_ok_and(|glob| glob.matches_path_with(file, match_options(&pattern)))
+        })
+        .map(String::as_str)
+}
+
+/// Describe where an error comes from, with the offending line of the file
+pub fn describe(config: &Config, main_caddyfile: &Path, location: &ErrorLocation) -> String {
+    let mut description = format!("{}:{}", location.file.display(), location.line);
+
+    let is_main = location.file.canonicalize().ok() == main_caddyfile.canonicalize().ok();
+    if is_main {
+        description.push_str(" (main Caddyfile)");
+    } else if let Some(folder) = importing_folder(config, &location.file) {
+        description.push_str(&format!(" (imported from folder '{}')", folder));
+    }
+
+    let source_line = fs::read_to_string(&location.file).ok().and_then(|content| {
+        content
+            .lines()
+            .nth(location.line.checked_sub(1)?)
+            .map(String::from)
+    });
+    if let Some(source_line) = source_line {
+        description.push_str(&format!("\n    {:>4} | {}", location.line, source_line));
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(folders: &[&str]) -> Config {
+        Config {
+            folders: folders.iter().map(|folder| folder.to_string()).collect(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn importing_folder_matches_one_level_per_wildcard() {
+        let config = config(&["/dev"]);
+        assert_eq!(
+            importing_folder(&config, Path::new("/dev/a/Caddyfile.dev")),
+            Some("/dev")
+        );
+        assert_eq!(
+            importing_folder(&config, Path::new("/dev/b/pkg/web/Caddyfile.dev")),
+            None
+        );
+    }
+
+    #[test]
+    fn importing_folder_matches_recursive_patterns_outside_hidden_dirs() {
+        let config = config(&["/dev", "/mono/**/Caddyfile.dev"]);
+        assert_eq!(
+            importing_folder(&config, Path::new("/mono/packages/web/Caddyfile.dev")),
+            Some("/mono/**/Caddyfile.dev")
+        );
+        assert_eq!(
+            importing_folder(&config, Path::new("/mono/.git/x/Caddyfile.dev")),
+            None
+        );
+    }
+
+    /// A main Caddyfile importing `dev/*/Caddyfile.dev`, with a broken `dev/app` project,
+    /// under a directory unique to the test
+    fn broken_setup(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let root = std::env::temp_dir().join(format!(
+            "caddy-dev-validate-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("dev/app")).unwrap();
+        let main = root.join("Caddyfile");
+        fs::write(
+            &main,
+            format!(
+                "{{\n\tadmin localhost:2019\n}}\n\nimport {}/dev/*/Caddyfile.dev\n",
+                root.display()
+            ),
+        )
+        .unwrap();
+        let app = root.join("dev/app/Caddyfile.dev");
+        fs::write(
+            &app,
+            "app.localhost {\n\treverse_proxy localhost:3000 {\n\t\tbogus\n\t}\n}\n",
+        )
+        .unwrap();
+        (root, main, app)
+    }
+
+    /// Output of `caddy validate` for [`broken_setup`], log lines first
+    fn validate_output(main: &Path, app: &Path) -> String {
+        format!(
+            concat!(
+                "{{\"level\":\"info\",\"ts\":1718000000.1,\"msg\":\"using config from file\",\"file\":\"{main}\"}}\n",
+                "Error: adapting config using caddyfile: parsing caddyfile tokens for 'reverse_proxy': ",
+                "{app}:3 - Error during parsing: unrecognized subdirective bogus, ",
+                "import chain ['{main}:5 (import {dir}/*/Caddyfile.dev)']\n",
+            ),
+            main = main.display(),
+            app = app.display(),
+            dir = app.parent().unwrap().parent().unwrap().display(),
+        )
+    }
+
+    #[test]
+    fn error_message_skips_log_lines() {
+        let output = "{\"level\":\"info\",\"msg\":\"using config from file\"}\n\
+                      Error: adapting config using caddyfile: bad\n";
+        assert_eq!(
+            error_message(output),
+            "Error: adapting config using caddyfile: bad"
+        );
+        assert_eq!(error_message("  something else\n"), "something else");
+    }
+
+    #[test]
+    fn locate_lists_the_innermost_file_first() {
+        let (root, main, app) = broken_setup("locate");
+        let message = error_message(&validate_output(&main, &app));
+
+        assert_eq!(
+            locate(&message),
+            [
+                ErrorLocation {
+                    file: app.clone(),
+                    line: 3
+                },
+                ErrorLocation {
+                    file: main.clone(),
+                    line: 5
+                },
+            ]
+        );
+        // References to files that don't exist are ignored
+        assert!(locate("Error: /nonexistent/Caddyfile.dev:3: unknown").is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn describe_names_the_folder_and_quotes_the_line() {
+        let (root, main, app) = broken_setup("describe");
+        let dev = root.join("dev").to_string_lossy().into_owned();
+        let config = config(&[&dev]);
+
+        assert_eq!(
+            describe(
+                &config,
+                &main,
+                &ErrorLocation {
+                    file: app.clone(),
+                    line: 3
+                }
+            ),
+            format!(
+                "{}:3 (imported from folder '{}')\n       3 | \t\tbogus",
+                app.display(),
+                dev
+            )
+        );
+        assert_eq!(
+            describe(
+                &config,
+                &main,
+                &ErrorLocation {
+                    file: main.clone(),
+                    line: 5
+                }
+            ),
+            format!(
+                "{}:5 (main Caddyfile)\n       5 | import {}/*/Caddyfile.dev",
+                main.display(),
+                dev
+            )
+        );
+        // Past the end of the file, only the location is shown
+        assert_eq!(
+            describe(
+                &config,
+                &main,
+                &ErrorLocation {
+                    file: app.clone(),
+                    line: 99
+                }
+            ),
+            format!("{}:99 (imported from folder '{}')", app.display(), dev)
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,287 @@
+// src/vars.rs
+//! Variable sources for `generate`: `--var` arguments, per-project vars files and
+//! dotenv files.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::CaddyDevError;
+use crate::template;
+
+/// Default name of the per-project variables file, looked up in the output directory
+pub const VARS_FILE_NAME: &str = "Caddyfile.vars";
+
+/// Parse a single key=value pair
+pub fn parse_key_val(s: &str) -> Result<(String, String), String> {
+    let parts: Vec<&str> = s.splitn(2, '=').collect();
+    if parts.len() != 2 {
+        return Err(format!(
+            "Invalid variable format: '{}'. Expected key=value",
+            s
+        ));
+    }
+    Ok((parts[0].to_string(), parts[1].to_string()))
+}
+
+/// Check that a global variable name can be used in a placeholder
+pub fn parse_var_name(s: &str) -> Result<String, String> {
+    if template::is_identifier(s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!("Invalid variable name: '{}'", s))
+    }
+}
+
+/// Parse the content of a vars file: one `key=value` per line, `#` comments and
+/// blank lines are ignored, whitespace around keys and values is trimmed.
+pub fn parse_vars_file(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = parse_key_val(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("line {}: Empty variable name", index + 1));
+        }
+        vars.push((key.to_string(), value.trim().to_string()));
+    }
+    Ok(vars)
+}
+
+/// Read and parse a vars file
+pub fn read_vars_file(path: &Path) -> Result<Vec<(String, String)>, CaddyDevError> {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| parse_vars_file(&content))
+        .map_err(|message| CaddyDevError::VarsFile {
+            path: path.to_path_buf(),
+            message,
+        })
+}
+
+/// Prefix of template variables resolved from the environment (`{{env.PORT}}`)
+pub const ENV_PREFIX: &str = "env.";
+
+/// Dotenv files loaded by `generate --dotenv`, in increasing order of precedence
+pub const DOTENV_FILE_NAMES: [&str; 2] = [".env", ".env.local"];
+
+/// Parse the content of a dotenv file.
+///
+/// Supports `#` comments, an optional `export` prefix, single-quoted (literal)
+/// values, double-quoted values with `\n`, `\t`, `\"` and `\\` escapes that may
+/// span several lines, and unquoted values with trailing ` # comments`.
+pub fn parse_dotenv(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    let mut chars = content.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        // Skip leading whitespace and blank lines
+        while let Some(&c) = chars.peek() {
+            if c == '\n' {
+                line += 1;
+            } else if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+
+        // Read the rest of the key part up to '=' or end of line
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c == '\n' {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        let key = key.trim();
+        if key.is_empty() && chars.peek().is_none() {
+            break;
+        }
+        if key.starts_with('#') {
+            while chars.peek().is_some_and(|&c| c != '\n') {
+                chars.next();
+            }
+            continue;
+        }
+        let key = key.strip_prefix("export ").unwrap_or(key).trim();
+        if chars.next() != Some('=') {
+            return Err(format!(
+                "line {}: Invalid line '{}'. Expected KEY=VALUE",
+                line, key
+            ));
+        }
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: Invalid variable name '{}'", line, key));
+        }
+
+        // Skip spaces between '=' and the value
+        while chars.peek().is_some_and(|&c| c == ' ' || c == '\t') {
+            chars.next();
+        }
+
+        let start_line = line;
+        let value = match chars.peek() {
+            Some(&quote @ ('"' | '\'')) => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(format!(
+                                "line {}: Unterminated quoted value for '{}'",
+                                start_line, key
+                            ));
+                        }
+                        Some(c) if c == quote => break,
+                        Some('\\') if quote == '"' => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some('r') => value.push('\r'),
+                            Some(c @ ('"' | '\\' | '$')) => value.push(c),
+                            Some(c) => {
+                                value.push('\\');
+                                value.push(c);
+                            }
+                            None => continue,
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            value.push(c);
+                        }
+                    }
+                }
+                // Ignore anything after the closing quote (usually a comment)
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+                value
+            }
+            _ => {
+                let mut value = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+                // Strip inline comments, which must be preceded by whitespace
+                if let Some(i) = value.find(" #").or_else(|| value.find("\t#")) {
+                    value.truncate(i);
+                }
+                value.trim().to_string()
+            }
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+/// Read and parse a dotenv file
+pub fn read_dotenv(path: &Path) -> Result<Vec<(String, String)>, CaddyDevError> {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| parse_dotenv(&content))
+        .map_err(|message| CaddyDevError::VarsFile {
+            path: path.to_path_buf(),
+            message,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_key_val_splits_on_first_equals() {
+        assert_eq!(
+            parse_key_val("url=http://a?b=c"),
+            Ok(("url".to_string(), "http://a?b=c".to_string()))
+        );
+        assert!(parse_key_val("novalue").is_err());
+    }
+
+    #[test]
+    fn parse_vars_file_skips_comments_and_trims() {
+        let content = "# comment\n\nsubdomain = app\n  port=3000  \nempty=\n";
+        assert_eq!(
+            parse_vars_file(content).unwrap(),
+            pairs(&[("subdomain", "app"), ("port", "3000"), ("empty", "")])
+        );
+    }
+
+    #[test]
+    fn parse_vars_file_reports_line_numbers() {
+        let error = parse_vars_file("a=1\n# comment\nbroken\n").unwrap_err();
+        assert!(error.starts_with("line 3: "), "{}", error);
+
+        let error = parse_vars_file("a=1\n = 2\n").unwrap_err();
+        assert_eq!(error, "line 2: Empty variable name");
+    }
+
+    #[test]
+    fn parse_dotenv_reads_plain_and_exported_values() {
+        let content = "# comment\nexport PORT=3000\nHOST = localhost # inline\nURL=http://a#b\n";
+        assert_eq!(
+            parse_dotenv(content).unwrap(),
+            pairs(&[
+                ("PORT", "3000"),
+                ("HOST", "localhost"),
+                ("URL", "http://a#b")
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_dotenv_handles_quotes_and_escapes() {
+        let content = concat!(
+            "SINGLE='literal \\n $x' # comment\n",
+            "DOUBLE=\"a\\tb\\n\\\"c\\\" \\\\\"\n",
+            "MULTI=\"first\nsecond\"\n",
+            "AFTER=ok\n",
+        );
+        assert_eq!(
+            parse_dotenv(content).unwrap(),
+            pairs(&[
+                ("SINGLE", "literal \\n $x"),
+                ("DOUBLE", "a\tb\n\"c\" \\"),
+                ("MULTI", "first\nsecond"),
+                ("AFTER", "ok"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_dotenv_reports_unterminated_quote() {
+        let error = parse_dotenv("A=1\nB=\"open\nstill open\n").unwrap_err();
+        assert_eq!(error, "line 2: Unterminated quoted value for 'B'");
+    }
+
+    #[test]
+    fn parse_dotenv_reports_invalid_lines() {
+        let error = parse_dotenv("A=1\nnot a pair\n").unwrap_err();
+        assert!(error.starts_with("line 2: Invalid line"), "{}", error);
+    }
+
+    #[test]
+    fn parse_var_name_accepts_placeholder_names() {
+        assert!(parse_var_name("base_domain").is_ok());
+        assert!(parse_var_name("bad name").is_err());
+        assert!(parse_var_name("").is_err());
+    }
+}
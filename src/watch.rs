@@ -0,0 +1,522 @@
+// src/watch.rs
+//! File watching for `caddy-dev watch` and `run`: inotify on Linux, polling elsewhere,
+//! and the loop regenerating and reloading changed projects.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::{Config, import_pattern, pattern_base};
+use crate::error::CaddyDevError;
+use crate::generate::{self, GenerateOptions, Generated};
+use crate::{reload, store, vars};
+
+/// Modification times of a set of files, `None` for files that don't exist
+pub type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Record the modification time of each file
+pub fn snapshot<'a>(files: impl IntoIterator<Item = &'a PathBuf>) -> Snapshot {
+    files
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+            (path.clone(), modified)
+        })
+        .collect()
+}
+
+/// Files that were created, modified or removed between two snapshots
+pub fn changed(before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let paths: BTreeSet<&PathBuf> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter(|path| before.get(*path).copied().flatten() != after.get(*path).copied().flatten())
+        .cloned()
+        .collect()
+}
+
+/// Everything `caddy-dev watch` keeps an eye on
+#[derive(Debug, Default)]
+pub struct Targets {
+    /// Project directories: those holding a template or an imported Caddyfile.dev
+    pub projects: BTreeSet<PathBuf>,
+    /// Files whose changes matter
+    pub files: BTreeSet<PathBuf>,
+    /// Directories to watch for changes to those files
+    pub dirs: BTreeSet<PathBuf>,
+}
+
+impl Targets {
+    /// Collect the projects matched by the configured folders, their templates, vars
+    /// files and Caddyfile.dev files, plus the config files themselves
+    pub fn collect(config: &Config, config_files: &[PathBuf]) -> Self {
+        let mut targets = Targets::default();
+
+        for file in config.imported_files() {
+            if let Some(dir) = file.path.parent() {
+                targets.projects.insert(dir.to_path_buf());
+            }
+            targets.files.insert(file.path);
+        }
+
+        // Projects with a template that was never generated aren't imported yet, and
+        // directories without one may get it later
+        for dir in config.project_dirs() {
+            if dir.join(&config.generate.template).is_file() {
+                targets.projects.insert(dir.clone());
+            }
+            targets.dirs.insert(dir);
+        }
+
+        // New project directories show up in the folders themselves
+        for folder in &config.folders {
+            let pattern = import_pattern(folder);
+            if let Some(dir_pattern) = Path::new(&pattern).parent() {
+                let base = pattern_base(dir_pattern);
+                if base.is_dir() {
+                    targets.dirs.insert(base);
+                }
+            }
+        }
+
+        for dir in &targets.projects {
+            targets.files.insert(dir.join(&config.generate.template));
+            targets.files.insert(dir.join(&config.generate.vars_file));
+            targets.files.insert(dir.join("Caddyfile.dev"));
+            if config.generate.dotenv {
+                for name in vars::DOTENV_FILE_NAMES {
+                    targets.files.insert(dir.join(name));
+                }
+            }
+            targets.dirs.insert(dir.clone());
+        }
+
+        for file in config_files {
+            targets.files.insert(file.clone());
+            if let Some(dir) = file.parent().filter(|dir| dir.is_dir()) {
+                targets.dirs.insert(dir.to_path_buf());
+            }
+        }
+
+        targets
+    }
+}
+
+/// Waits for changes in a set of directories (not recursive)
+pub struct Watcher {
+    backend: backend::Backend,
+}
+
+impl Watcher {
+    pub fn new() -> io::Result<Self> {
+        Ok(Watcher {
+            backend: backend::Backend::new()?,
+        })
+    }
+
+    /// Watch exactly these directories, dropping the ones no longer listed
+    pub fn watch_dirs(&mut self, dirs: &BTreeSet<PathBuf>) {
+        self.backend.watch_dirs(dirs);
+    }
+
+    /// Block until something changes in a watched directory, then until no further
+    /// change happens for `debounce`, so a burst of writes is handled once
+    pub fn wait(&mut self, debounce: Duration) -> io::Result<()> {
+        self.backend.wait(debounce)
+    }
+}
+
+/// What [`watch_projects`] is doing, for reporting progress
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// Watching started
+    Started { projects: usize, folders: usize },
+    /// config.toml changed and was loaded again
+    ConfigReloaded(PathBuf),
+    /// A project's Caddyfile.dev was regenerated
+    Generated(Generated),
+    /// A project's Caddyfile.dev could not be regenerated
+    GenerateFailed { dir: PathBuf, error: CaddyDevError },
+    /// Caddy was reloaded; holds the warnings of the Caddyfile adapter
+    Reloaded(Vec<String>),
+    /// config.toml could not be loaded or Caddy could not be reloaded; watching goes on
+    Failed(CaddyDevError),
+}
+
+/// Regenerate changed templates and reload Caddy (unless `reload` is false), reporting
+/// each step to `on_event`. Only returns when files can no longer be watched.
+pub fn watch_projects(
+    mut config: Config,
+    debounce: Duration,
+    reload: bool,
+    mut on_event: impl FnMut(WatchEvent),
+) -> Result<(), CaddyDevError> {
+    let config_path = store::get_config_path();
+    let config_files = [config_path.clone()];
+
+    let mut watcher = Watcher::new().map_err(CaddyDevError::Watch)?;
+
+    let mut targets = Targets::collect(&config, &config_files);
+    let mut before = snapshot(&targets.files);
+    watcher.watch_dirs(&targets.dirs);
+    on_event(WatchEvent::Started {
+        projects: targets.projects.len(),
+        folders: config.folders.len(),
+    });
+
+    loop {
+        watcher.wait(debounce).map_err(CaddyDevError::Watch)?;
+
+        // Collect again first, so templates in new project directories count as changes
+        targets = Targets::collect(&config, &config_files);
+        let changed = changed(&before, &snapshot(&targets.files));
+        let mut needs_reload = false;
+        let mut regenerate_all = false;
+
+        if changed.contains(&config_path) {
+            match Config::load(&config_path) {
+                Ok(Some(new_config)) => {
+                    // Global variables and generate defaults apply to every project
+                    regenerate_all =
+                        new_config.vars != config.vars || new_config.generate != config.generate;
+                    config = new_config;
+                    on_event(WatchEvent::ConfigReloaded(config_path.clone()));
+                    needs_reload = true;
+                }
+                Ok(None) => {}
+                Err(e) => on_event(WatchEvent::Failed(e)),
+            }
+        }
+
+        if regenerate_all {
+            for outcome in generate::generate_all(&GenerateOptions::default(), &config) {
+                match outcome.result {
+                    Ok(generated) => on_event(WatchEvent::Generated(generated)),
+                    Err(error) => on_event(WatchEvent::GenerateFailed {
+                        dir: outcome.dir,
+                        error,
+                    }),
+                }
+            }
+            needs_reload = true;
+        }
+
+        // Regenerate projects whose template or variables changed
+        let inputs = [&config.generate.template, &config.generate.vars_file];
+        let regenerate: BTreeSet<&Path> = changed
+            .iter()
+            .filter(|path| {
+                let name = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default();
+                inputs.iter().any(|input| name == input.as_str())
+                    || (config.generate.dotenv && vars::DOTENV_FILE_NAMES.contains(&name))
+            })
+            .filter_map(|path| path.parent())
+            .filter(|dir| !regenerate_all && dir.join(&config.generate.template).is_file())
+            .collect();
+        for dir in regenerate {
+            let options = GenerateOptions {
+                output_dir: Some(dir.to_path_buf()),
+                ..Default::default()
+            };
+            match generate::generate(options, &config) {
+                Ok(generated) => {
+                    on_event(WatchEvent::Generated(generated));
+                    needs_reload = true;
+                }
+                Err(error) => on_event(WatchEvent::GenerateFailed {
+                    dir: dir.to_path_buf(),
+                    error,
+                }),
+            }
+        }
+
+        // Caddyfile.dev files edited by hand, added or removed
+        if changed.iter().any(|path| {
+            path.file_name()
+                .is_some_and(|name| name == generate::OUTPUT_FILE_NAME)
+        }) {
+            needs_reload = true;
+        }
+
+        if needs_reload && reload {
+            match reload::apply(&config, &store::get_main_caddyfile_path()) {
+                Ok(warnings) => on_event(WatchEvent::Reloaded(warnings)),
+                Err(e) => on_event(WatchEvent::Failed(e)),
+            }
+        }
+
+        // Pick up new projects, and ignore the writes made above
+        targets = Targets::collect(&config, &config_files);
+        before = snapshot(&targets.files);
+        watcher.watch_dirs(&targets.dirs);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod backend {
+    use std::collections::{BTreeSet, HashMap};
+    use std::io;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use inotify::{Inotify, WatchDescriptor, WatchMask};
+
+    pub struct Backend {
+        inotify: Inotify,
+        watches: HashMap<PathBuf, WatchDescriptor>,
+        buffer: Vec<u8>,
+    }
+
+    impl Backend {
+        pub fn new() -> io::Result<Self> {
+            Ok(Backend {
+                inotify: Inotify::init()?,
+                watches: HashMap::new(),
+                buffer: vec![0; 4096],
+            })
+        }
+
+        pub fn watch_dirs(&mut self, dirs: &BTreeSet<PathBuf>) {
+            let removed: Vec<PathBuf> = self
+                .watches
+                .keys()
+                .filter(|dir| !dirs.contains(*dir))
+                .cloned()
+                .collect();
+            for dir in removed {
+                if let Some(wd) = self.watches.remove(&dir) {
+                    // Fails if the directory is gone, which already dropped the watch
+                    let _ = self.inotify.watches().remove(wd);
+                }
+            }
+
+            // Editors often save by writing a new file and renaming it over the old one
+            let mask = WatchMask::CLOSE_WRITE
+                | WatchMask::CREATE
+                | WatchMask::DELETE
+                | WatchMask::MODIFY
+                | WatchMask::MOVED_FROM
+                | WatchMask::MOVED_TO;
+            for dir in dirs {
+                if !self.watches.contains_key(dir)
+                    && let Ok(wd) = self.inotify.watches().add(dir, mask)
+                {
+                    self.watches.insert(dir.clone(), wd);
+                }
+            }
+        }
+
+        pub fn wait(&mut self, debounce: Duration) -> io::Result<()> {
+            self.inotify.read_events_blocking(&mut self.buffer)?;
+            loop {
+                std::thread::sleep(debounce);
+                match self.inotify.read_events(&mut self.buffer) {
+                    Ok(_) => continue,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod backend {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::{Snapshot, snapshot};
+
+    /// How often directories are scanned for changes
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub struct Backend {
+        dirs: BTreeSet<PathBuf>,
+        last: Snapshot,
+    }
+
+    impl Backend {
+        pub fn new() -> io::Result<Self> {
+            Ok(Backend {
+                dirs: BTreeSet::new(),
+                last: Snapshot::new(),
+            })
+        }
+
+        pub fn watch_dirs(&mut self, dirs: &BTreeSet<PathBuf>) {
+            self.dirs = dirs.clone();
+            self.last = self.scan();
+        }
+
+        pub fn wait(&mut self, debounce: Duration) -> io::Result<()> {
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+                let current = self.scan();
+                if current != self.last {
+                    self.last = current;
+                    break;
+                }
+            }
+            loop {
+                std::thread::sleep(debounce);
+                let current = self.scan();
+                if current == self.last {
+                    return Ok(());
+                }
+                self.last = current;
+            }
+        }
+
+        /// Modification times of every entry in the watched directories
+        fn scan(&self) -> Snapshot {
+            let entries: Vec<PathBuf> = self
+                .dirs
+                .iter()
+                .filter_map(|dir| fs::read_dir(dir).ok())
+                .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+                .collect();
+            snapshot(&entries)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory under the system temp dir, unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("caddy-dev-watch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn changed_lists_created_modified_and_removed_files() {
+        let dir = temp_dir("changed");
+        let [modified, removed, created, untouched, missing] =
+            ["modified", "removed", "created", "untouched", "missing"].map(|name| dir.join(name));
+        for path in [&modified, &removed, &untouched] {
+            touch(path);
+        }
+        let files = [&modified, &removed, &created, &untouched, &missing];
+        let before = snapshot(files);
+
+        fs::File::options()
+            .write(true)
+            .open(&modified)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        fs::remove_file(&removed).unwrap();
+        touch(&created);
+
+        let after = snapshot(files);
+        assert_eq!(before[&missing], None);
+        assert_eq!(changed(&before, &after), [created, modified, removed]);
+        assert!(changed(&after, &after).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn changed_counts_files_missing_from_a_snapshot() {
+        let dir = temp_dir("new-target");
+        let existing = dir.join("existing");
+        touch(&existing);
+        let absent = dir.join("absent");
+
+        let before = Snapshot::new();
+        let after = snapshot([&existing, &absent]);
+
+        assert_eq!(changed(&before, &after), [existing]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn collect_finds_projects_their_files_and_dirs() {
+        let root = temp_dir("collect");
+        let dev = root.join("dev");
+        touch(&dev.join("a/Caddyfile.dev"));
+        touch(&dev.join("b/Caddyfile.template"));
+        fs::create_dir_all(dev.join("c")).unwrap();
+        let config_file = root.join("config.toml");
+        let mut config = Config {
+            folders: vec![dev.to_string_lossy().into_owned()],
+            ..Config::default()
+        };
+        config.generate.dotenv = true;
+
+        let targets = Targets::collect(&config, std::slice::from_ref(&config_file));
+
+        assert_eq!(
+            targets.projects,
+            BTreeSet::from([dev.join("a"), dev.join("b")])
+        );
+        for project in ["a", "b"] {
+            for name in [
+                "Caddyfile.dev",
+                "Caddyfile.template",
+                "Caddyfile.vars",
+                ".env",
+                ".env.local",
+            ] {
+                let file = dev.join(project).join(name);
+                assert!(targets.files.contains(&file), "{}", file.display());
+            }
+        }
+        assert!(targets.files.contains(&config_file));
+        assert!(
+            !targets
+                .files
+                .iter()
+                .any(|file| file.starts_with(dev.join("c")))
+        );
+        assert_eq!(
+            targets.dirs,
+            BTreeSet::from([
+                root.clone(),
+                dev.clone(),
+                dev.join("a"),
+                dev.join("b"),
+                dev.join("c")
+            ])
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn new_project_directories_show_up_as_changes() {
+        let root = temp_dir("new-project");
+        let dev = root.join("dev");
+        touch(&dev.join("a/Caddyfile.dev"));
+        let config = Config {
+            folders: vec![dev.to_string_lossy().into_owned()],
+            ..Config::default()
+        };
+        let targets = Targets::collect(&config, &[]);
+        let before = snapshot(&targets.files);
+
+        let template = dev.join("new/Caddyfile.template");
+        touch(&template);
+        let targets = Targets::collect(&config, &[]);
+
+        assert!(targets.projects.contains(&dev.join("new")));
+        assert_eq!(changed(&before, &snapshot(&targets.files)), [template]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}